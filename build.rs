@@ -81,15 +81,6 @@ fn download_ffmpeg() -> Result<String, Box<dyn Error>> {
 }
 
 fn main() {
-    let target = "allsorts/src/binary/read.rs";
-    // 修改本地代码
-    let s = std::fs::read_to_string(&target).expect("please run [git submodule update --remote]");
-
-    std::fs::write(
-        &target,
-        s.replace("    base: usize,", "    pub base: usize,"),
-    )
-    .expect("update read.rs fail");
     let out = std::env::var("OUT_DIR").unwrap();
     #[cfg(target_os = "windows")]
     {
@@ -0,0 +1,322 @@
+//! 批量子集化
+//!
+//! 同一段文本并行子集化多个字体，输出路径由模板生成，最后输出每个字体的结果
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use allsorts::tables::FontTableProvider;
+use allsorts::tag;
+
+use fontview::font_info::{self, FontError, OutputFormat, SubsetOptions, SubsetReport};
+
+/// 展开输入中的 glob，例如 `fonts/**/*.otf`，不包含通配符的输入原样保留
+pub(crate) fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        if !input.contains(['*', '?']) {
+            paths.push(PathBuf::from(input));
+            continue;
+        }
+        // 第一个包含通配符的部分之前是遍历的目录
+        let normalized = input.replace('\\', "/");
+        let parts = normalized.split('/').collect::<Vec<_>>();
+        let fixed = parts
+            .iter()
+            .position(|p| p.contains(['*', '?']))
+            .unwrap_or(parts.len());
+        let dir = match parts[..fixed].join("/") {
+            d if !d.is_empty() => d,
+            _ if normalized.starts_with('/') => "/".to_string(),
+            _ => ".".to_string(),
+        };
+        let pattern = parts[fixed..].join("/");
+        // 不包含 `/` 的规则只匹配文件名，这里需要匹配相对路径
+        let pattern = if pattern.contains('/') {
+            pattern
+        } else {
+            format!("./{pattern}")
+        };
+        let files =
+            fontview::corpus::files(Path::new(&dir)).map_err(|e| format!("{input}: {e}"))?;
+        let matched = files
+            .into_iter()
+            .filter(|(relative, _)| fontview::corpus::matches(&pattern, relative))
+            .map(|(_, path)| path)
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            return Err(format!("no font matches {input}"));
+        }
+        paths.extend(matched);
+    }
+    // 同一个文件只处理一次
+    let mut seen = HashSet::new();
+    paths.retain(|p| seen.insert(p.clone()));
+    Ok(paths)
+}
+
+/// 输出模板中可用的名称
+struct TemplateNames<'a> {
+    family: &'a str,
+    subfamily: &'a str,
+    stem: &'a str,
+    ext: &'a str,
+    index: usize,
+}
+
+/// 生成输出路径，支持 `{family}`、`{subfamily}`、`{stem}`、`{ext}`、`{index}`
+///
+/// directory 为 true 时模板是目录，等同于 `目录/{stem}.{ext}`
+fn output_path(template: &str, directory: bool, names: &TemplateNames) -> PathBuf {
+    let template = if !directory {
+        template.to_string()
+    } else {
+        Path::new(template)
+            .join("{stem}.{ext}")
+            .display()
+            .to_string()
+    };
+    // 名称中不能出现路径分隔符等文件名不允许的字符
+    let clean = |s: &str| {
+        s.chars()
+            .map(|c| {
+                if c.is_control() || "/\\:*?\"<>|".contains(c) {
+                    '_'
+                } else {
+                    c
+                }
+            })
+            .collect::<String>()
+    };
+    PathBuf::from(
+        template
+            .replace("{family}", &clean(names.family))
+            .replace("{subfamily}", &clean(names.subfamily))
+            .replace("{stem}", &clean(names.stem))
+            .replace("{ext}", names.ext)
+            .replace("{index}", &names.index.to_string()),
+    )
+}
+
+/// 字体的输出路径，Auto 格式时保持 woff、woff2 输入的格式
+pub(crate) fn output_of<F: FontTableProvider>(
+    template: &str,
+    directory: bool,
+    input: &Path,
+    index: usize,
+    provider: &F,
+    format: OutputFormat,
+) -> PathBuf {
+    let names = font_info::names(provider).unwrap_or_default();
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let cff = provider.read_table_data(tag::CFF).is_ok();
+    let ext = match format {
+        OutputFormat::Auto => match input
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("woff") => "woff",
+            Some("woff2") => "woff2",
+            _ => OutputFormat::Sfnt.extension(cff),
+        },
+        format => format.extension(cff),
+    };
+    output_path(
+        template,
+        directory,
+        &TemplateNames {
+            family: &names.family,
+            subfamily: &names.subfamily,
+            stem: &stem,
+            ext,
+            index,
+        },
+    )
+}
+
+/// 记录已经使用的输出路径，多个字体输出到同一个文件时报错
+pub(crate) fn claim(claimed: &mut HashSet<PathBuf>, output: &Path) -> Result<(), FontError> {
+    if !claimed.insert(output.to_path_buf()) {
+        return Err(FontError::Usage(format!(
+            "output {} is used by another font, add {{index}} or {{subfamily}} to --output",
+            output.display()
+        )));
+    }
+    Ok(())
+}
+
+/// 单个字体的结果
+pub(crate) struct Summary {
+    pub input: PathBuf,
+    /// 字体集合中的下标
+    pub face: usize,
+    /// 输入文件大小
+    pub input_size: u64,
+    pub output: Option<PathBuf>,
+    pub missing: Vec<char>,
+    pub result: Result<SubsetReport, FontError>,
+}
+
+/// 批量子集化的参数
+pub(crate) struct Batch<'a> {
+    pub output: &'a str,
+    pub face: Option<&'a str>,
+    /// 用于检查缺失字符的文本
+    pub text: &'a str,
+    /// 实际子集化的文本，包含范围和字符集
+    pub subset: &'a str,
+    /// 有缺失字符时不输出
+    pub strict: bool,
+    pub options: &'a SubsetOptions,
+    /// 并行的线程数
+    pub jobs: usize,
+}
+
+impl Batch<'_> {
+    /// 并行处理所有字体，结果和输入的顺序一致
+    pub(crate) fn run(&self, inputs: &[PathBuf]) -> Vec<Summary> {
+        let next = AtomicUsize::new(0);
+        let claimed = Mutex::new(HashSet::new());
+        let results = Mutex::new(Vec::with_capacity(inputs.len()));
+        // 不是模板的输出在多个输入或者已经是目录时作为输出目录
+        let directory =
+            !self.output.contains('{') && (inputs.len() > 1 || Path::new(self.output).is_dir());
+        std::thread::scope(|s| {
+            for _ in 0..self.jobs.clamp(1, inputs.len().max(1)) {
+                s.spawn(|| {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::SeqCst);
+                        let Some(input) = inputs.get(index) else {
+                            break;
+                        };
+                        let summary = self.process(index, input, directory, &claimed);
+                        results.lock().unwrap().push((index, summary));
+                    }
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, summary)| summary).collect()
+    }
+
+    fn process(
+        &self,
+        index: usize,
+        input: &Path,
+        directory: bool,
+        claimed: &Mutex<HashSet<PathBuf>>,
+    ) -> Summary {
+        let mut summary = Summary {
+            input: input.to_path_buf(),
+            face: 0,
+            input_size: 0,
+            output: None,
+            missing: Vec::new(),
+            result: Err(FontError::EmptyGlyphSet),
+        };
+        summary.result = self.do_process(index, input, directory, claimed, &mut summary);
+        summary
+    }
+
+    fn do_process(
+        &self,
+        index: usize,
+        input: &Path,
+        directory: bool,
+        claimed: &Mutex<HashSet<PathBuf>>,
+        summary: &mut Summary,
+    ) -> Result<SubsetReport, FontError> {
+        let font = std::fs::read(input).map_err(|e| FontError::io(input, e))?;
+        summary.input_size = font.len() as u64;
+        let font_file = font_info::load(&font)?;
+        let face = match self.face {
+            Some(face) => font_info::find_face(&font, face)
+                .ok_or_else(|| FontError::Usage(format!("face not found: {face}")))?,
+            None => 0,
+        };
+        summary.face = face;
+        let provider = font_file.table_provider(face)?;
+
+        let output = output_of(
+            self.output,
+            directory,
+            input,
+            index,
+            &provider,
+            self.options.format,
+        );
+        claim(&mut claimed.lock().unwrap(), &output)?;
+        summary.output = Some(output.clone());
+
+        summary.missing = font_info::missing_chars(&provider, self.text)?;
+        if self.strict && !summary.missing.is_empty() {
+            return Err(FontError::Missing(summary.missing.len()));
+        }
+        if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| FontError::write(parent, e))?;
+        }
+        font_info::subset_text(&provider, self.subset, &output, self.options)
+    }
+}
+
+/// 输出每个字体结果的表格
+pub(crate) fn print_summary(summaries: &[Summary]) {
+    let rows = summaries
+        .iter()
+        .map(|s| {
+            [
+                s.input.display().to_string(),
+                s.output
+                    .as_ref()
+                    .map(|o| o.display().to_string())
+                    .unwrap_or_default(),
+                s.result
+                    .as_ref()
+                    .map(|r| r.family.clone())
+                    .unwrap_or_default(),
+                s.result
+                    .as_ref()
+                    .map(|r| format!("{:.1} KiB", r.size as f64 / 1024.0))
+                    .unwrap_or_default(),
+                s.missing.len().to_string(),
+                match &s.result {
+                    Ok(_) => "ok".to_string(),
+                    Err(e) => e.to_string(),
+                },
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(
+        &["INPUT", "OUTPUT", "FAMILY", "SIZE", "MISSING", "STATUS"],
+        &rows,
+    );
+    let failed = summaries.iter().filter(|s| s.result.is_err()).count();
+    println!("{} fonts, {} failed", summaries.len(), failed);
+}
+
+/// 按列对齐输出表格
+pub(crate) fn print_table<const N: usize>(header: &[&str; N], rows: &[[String; N]]) {
+    let header = header.map(String::from);
+    let mut widths = [0; N];
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        for (w, cell) in widths.iter_mut().zip(row.iter()) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, w)| format!("{cell}{}", " ".repeat(w - cell.chars().count())))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
@@ -0,0 +1,151 @@
+//! 内置字符集
+//!
+//! 汉字、假名、谚文等字符集由 encoding_rs 解码对应的传统编码区间得到，不需要额外携带数据文件
+
+use encoding_rs::{BIG5, EUC_JP, EUC_KR, Encoding, GBK};
+
+/// 内置字符集
+pub struct Charset {
+    /// 命令行中使用的名称
+    pub name: &'static str,
+    /// 界面上显示的说明
+    pub description: &'static str,
+    build: fn() -> String,
+}
+
+impl Charset {
+    /// 字符集包含的所有字符
+    pub fn chars(&self) -> String {
+        (self.build)()
+    }
+}
+
+/// 所有内置字符集
+pub const CHARSETS: &[Charset] = &[
+    Charset {
+        name: "ascii",
+        description: "ASCII 可打印字符",
+        build: || ranges(&[(0x20, 0x7e)]),
+    },
+    Charset {
+        name: "latin-1",
+        description: "ASCII 和 Latin-1 补充",
+        build: || ranges(&[(0x20, 0x7e), (0xa0, 0xff)]),
+    },
+    Charset {
+        name: "cjk-punctuation",
+        description: "中日韩标点和全角标点",
+        build: || {
+            ranges(&[
+                (0xb7, 0xb7),
+                (0x2014, 0x2014),
+                (0x2018, 0x201f),
+                (0x2026, 0x2026),
+                (0x3000, 0x303f),
+                (0xff01, 0xff0f),
+                (0xff1a, 0xff20),
+                (0xff3b, 0xff40),
+                (0xff5b, 0xff65),
+            ])
+        },
+    },
+    Charset {
+        name: "kana",
+        description: "平假名和片假名",
+        build: || ranges(&[(0x3041, 0x3096), (0x3099, 0x30ff)]),
+    },
+    Charset {
+        name: "gb2312",
+        description: "GB2312 符号和全部汉字",
+        build: || double_byte(GBK, &[(0xa1, 0xa9), (0xb0, 0xf7)], &[(0xa1, 0xfe)]),
+    },
+    Charset {
+        name: "gb2312-level1",
+        description: "GB2312 一级汉字（3755 个常用字）",
+        build: || double_byte(GBK, &[(0xb0, 0xd7)], &[(0xa1, 0xfe)]),
+    },
+    Charset {
+        name: "gb2312-level2",
+        description: "GB2312 二级汉字（3008 个次常用字）",
+        build: || double_byte(GBK, &[(0xd8, 0xf7)], &[(0xa1, 0xfe)]),
+    },
+    Charset {
+        name: "big5-common",
+        description: "Big5 常用字（5401 字）",
+        build: || {
+            // A440-C67E
+            double_byte(BIG5, &[(0xa4, 0xc5)], &[(0x40, 0x7e), (0xa1, 0xfe)])
+                + &double_byte(BIG5, &[(0xc6, 0xc6)], &[(0x40, 0x7e)])
+        },
+    },
+    Charset {
+        name: "jis-x-0208",
+        description: "JIS X 0208 全部字符",
+        build: || double_byte(EUC_JP, &[(0xa1, 0xa8), (0xb0, 0xf4)], &[(0xa1, 0xfe)]),
+    },
+    Charset {
+        name: "jis-x-0208-kanji",
+        description: "JIS X 0208 汉字（第一、第二水准）",
+        build: || double_byte(EUC_JP, &[(0xb0, 0xf4)], &[(0xa1, 0xfe)]),
+    },
+    Charset {
+        name: "ks-x-1001-hangul",
+        description: "KS X 1001 谚文（2350 字）",
+        build: || double_byte(EUC_KR, &[(0xb0, 0xc8)], &[(0xa1, 0xfe)]),
+    },
+];
+
+/// 根据名称查找字符集
+pub fn find(name: &str) -> Option<&'static Charset> {
+    CHARSETS
+        .iter()
+        .find(|c| c.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// 合并多个字符集，名称用逗号分隔
+pub fn chars_of(names: &str) -> Result<String, String> {
+    let mut text = String::new();
+    for name in names.split(',').filter(|n| !n.trim().is_empty()) {
+        let charset = find(name).ok_or_else(|| {
+            format!(
+                "unknown charset {name}, expect one of {}",
+                CHARSETS
+                    .iter()
+                    .map(|c| c.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?;
+        text.push_str(&charset.chars());
+    }
+    Ok(text)
+}
+
+fn ranges(ranges: &[(u32, u32)]) -> String {
+    ranges
+        .iter()
+        .flat_map(|(start, end)| *start..=*end)
+        .filter_map(char::from_u32)
+        .collect()
+}
+
+/// 逐个解码双字节编码区间，跳过未分配和映射到私用区的码位
+fn double_byte(encoding: &'static Encoding, leads: &[(u8, u8)], trails: &[(u8, u8)]) -> String {
+    let mut text = String::new();
+    for lead in leads.iter().flat_map(|(start, end)| *start..=*end) {
+        for trail in trails.iter().flat_map(|(start, end)| *start..=*end) {
+            let bytes = [lead, trail];
+            let (decoded, had_errors) = encoding.decode_without_bom_handling(&bytes);
+            if had_errors {
+                continue;
+            }
+            let mut chars = decoded.chars();
+            if let (Some(c), None) = (chars.next(), chars.next())
+                && !('\u{e000}'..='\u{f8ff}').contains(&c)
+            {
+                text.push(c);
+            }
+        }
+    }
+    text
+}
@@ -0,0 +1,82 @@
+//! `fontview cli check`，检查字体能否显示文本中的所有字符
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use fontview::blocks;
+use fontview::font_info::{self, FontError};
+
+/// 输出字体缺少的字符，按 Unicode 区块分组，有缺失字符时返回 [`FontError::Missing`]
+pub(crate) fn run(path: &Path, face: Option<&str>, text: &str) -> Result<(), FontError> {
+    let data = std::fs::read(path).map_err(|e| FontError::io(path, e))?;
+    let font = font_info::load(&data)?;
+    let index = match face {
+        Some(face) => font_info::find_face(&data, face)
+            .ok_or_else(|| FontError::Usage(format!("face not found: {face}")))?,
+        None => 0,
+    };
+    let provider = font.table_provider(index)?;
+    let mut missing = font_info::missing_chars(&provider, text)?;
+    missing.sort();
+    missing.dedup();
+    // 不支持的变体序列会报告其中的选择符，选择符单独列出，不计入字符
+    let (selectors, missing): (Vec<_>, Vec<_>) = missing
+        .into_iter()
+        .partition(|c| font_info::is_selector(*c));
+
+    let mut checked = text
+        .chars()
+        .filter(|c| !c.is_control() && !font_info::is_selector(*c))
+        .collect::<Vec<_>>();
+    checked.sort();
+    checked.dedup();
+    println!(
+        "{}: {} of {} characters covered",
+        path.display(),
+        checked.len().saturating_sub(missing.len()),
+        checked.len()
+    );
+    if !selectors.is_empty() {
+        println!(
+            "unsupported variation selectors: {}",
+            selectors
+                .iter()
+                .map(|c| format!("U+{:04X}", *c as u32))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+    }
+
+    // 以区块的起始码位排序，未分配区块的字符放在最后
+    let mut groups: BTreeMap<u32, (&str, Vec<char>)> = BTreeMap::new();
+    for c in missing.iter() {
+        let (start, name) = match blocks::block_of(*c) {
+            Some(b) => (b.start, b.name),
+            None => (u32::MAX, "No Block"),
+        };
+        groups.entry(start).or_insert((name, Vec::new())).1.push(*c);
+    }
+    for (name, mut chars) in groups.into_values() {
+        chars.sort();
+        println!();
+        println!(
+            "{name} ({} missing): {}",
+            chars.len(),
+            chars.iter().collect::<String>()
+        );
+        println!(
+            "  {}",
+            chars
+                .iter()
+                .map(|c| format!("U+{:04X}", *c as u32))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+    }
+
+    if missing.is_empty() && selectors.is_empty() {
+        Ok(())
+    } else {
+        Err(FontError::Missing(missing.len() + selectors.len()))
+    }
+}
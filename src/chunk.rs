@@ -0,0 +1,137 @@
+//! 按 unicode-range 把字体切分为多个分片，并生成对应的 CSS
+//!
+//! 字符按使用频率排序后再切分，常用字集中在前面的分片中，浏览器只需要下载页面用到的分片
+
+use std::collections::HashSet;
+
+use crate::charset;
+
+/// 内置的频率分层，越靠前越常用，没有出现在这里的字符按码位排在最后
+const TIERS: &[&str] = &[
+    "latin-1",
+    "cjk-punctuation",
+    "gb2312-level1",
+    "kana",
+    "ks-x-1001-hangul",
+    "big5-common",
+    "jis-x-0208-kanji",
+    "gb2312-level2",
+];
+
+/// 把字体包含的字符按频率排序，priority 中的字符按出现顺序排在最前面
+pub(crate) fn order(covered: &[char], priority: &str) -> Vec<char> {
+    let covered = covered.iter().copied().collect::<HashSet<_>>();
+    let mut seen = HashSet::with_capacity(covered.len());
+    let mut ordered = Vec::with_capacity(covered.len());
+    let tiers = TIERS
+        .iter()
+        .filter_map(|name| charset::find(name))
+        .map(|c| c.chars())
+        .collect::<Vec<_>>();
+    for c in priority.chars().chain(tiers.iter().flat_map(|t| t.chars())) {
+        if covered.contains(&c) && seen.insert(c) {
+            ordered.push(c);
+        }
+    }
+    let mut rest = covered
+        .into_iter()
+        .filter(|c| !seen.contains(c))
+        .collect::<Vec<_>>();
+    rest.sort();
+    ordered.extend(rest);
+    ordered
+}
+
+/// 把有序的字符平均切分为最多 count 个分片，分片内按码位排序以便合并为更短的范围
+pub(crate) fn split(ordered: &[char], count: usize) -> Vec<Vec<char>> {
+    let size = ordered.len().div_ceil(count.max(1)).max(1);
+    ordered
+        .chunks(size)
+        .map(|chunk| {
+            let mut chunk = chunk.to_vec();
+            chunk.sort();
+            chunk
+        })
+        .collect()
+}
+
+/// 生成 CSS `unicode-range` 的值，连续的码位合并为一个范围
+pub(crate) fn unicode_range(chars: &[char]) -> String {
+    let mut codes = chars.iter().map(|c| *c as u32).collect::<Vec<_>>();
+    codes.sort();
+    codes.dedup();
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for code in codes {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == code => *end = code,
+            _ => ranges.push((code, code)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                format!("U+{start:X}")
+            } else {
+                format!("U+{start:X}-{end:X}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 一个分片对应的 `@font-face`
+pub(crate) struct FontFace {
+    /// 相对 CSS 文件的字体地址
+    pub url: String,
+    /// `format()` 中的格式名称
+    pub format: &'static str,
+    pub unicode_range: String,
+}
+
+/// 生成每个分片一个 `@font-face` 的 CSS
+pub(crate) fn css(family: &str, weight: u16, italic: bool, faces: &[FontFace]) -> String {
+    let family = family.replace('\\', "\\\\").replace('"', "\\\"");
+    let url = |url: &str| url.replace('\\', "\\\\").replace('"', "\\\"");
+    faces
+        .iter()
+        .map(|face| {
+            format!(
+                "@font-face {{\n  font-family: \"{family}\";\n  font-style: {};\n  font-weight: {weight};\n  font-display: swap;\n  src: url(\"{}\") format(\"{}\");\n  unicode-range: {};\n}}\n",
+                if italic { "italic" } else { "normal" },
+                url(&face.url),
+                face.format,
+                face.unicode_range,
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_range_merges_consecutive() {
+        assert_eq!(
+            unicode_range(&['中', 'c', 'a', 'b', 'e', 'a']),
+            "U+61-63, U+65, U+4E2D"
+        );
+        assert_eq!(unicode_range(&['\u{1f600}']), "U+1F600");
+        assert_eq!(unicode_range(&[]), "");
+    }
+
+    #[test]
+    fn order_puts_priority_first() {
+        let ordered = order(&['中', 'z', 'a', '\u{1f600}'], "z中");
+        assert_eq!(ordered, ['z', '中', 'a', '\u{1f600}']);
+    }
+
+    #[test]
+    fn split_into_sorted_chunks() {
+        let chunks = split(&['c', 'a', 'b', 'e', 'd'], 2);
+        assert_eq!(chunks, [vec!['a', 'b', 'c'], vec!['d', 'e']]);
+        assert_eq!(split(&['a'], 3), [vec!['a']]);
+    }
+}
@@ -0,0 +1,471 @@
+//! 子集化任务配置文件
+//!
+//! ```toml
+//! [defaults]
+//! format = "woff2"
+//!
+//! [[job]]
+//! name = "site"
+//! input = ["fonts/*.otf"]
+//! output = "dist/{stem}.{ext}"
+//! files = ["copy.txt"]
+//! charsets = ["ascii", "cjk-punctuation"]
+//! ```
+//!
+//! 输入字体、字符和配置都没有变化的任务会被跳过，上次的结果记录在配置文件同目录的
+//! `.fontview-cache.toml` 中
+
+use std::path::{Path, PathBuf};
+
+use crate::batch::{self, Batch};
+use fontview::font_info::{self, FNV_OFFSET, FontError, NameSeed, SubsetOptions, fnv1a};
+use fontview::{charset, corpus};
+
+/// 任务中可以使用的键
+const KEYS: &[&str] = &[
+    "name",
+    "input",
+    "output",
+    "face",
+    "text",
+    "files",
+    "corpus",
+    "include",
+    "exclude",
+    "unicodes",
+    "charsets",
+    "format",
+    "profile",
+    "keep_tables",
+    "drop_tables",
+    "features",
+    "family",
+    "seed",
+    "deterministic",
+    "strict",
+    "jobs",
+];
+
+const CACHE_FILE: &str = ".fontview-cache.toml";
+
+/// 执行配置文件中的所有任务，返回进程退出码
+///
+/// force 为 true 时不跳过没有变化的任务
+pub(crate) fn run(path: &Path, force: bool) -> i32 {
+    match do_run(path, force) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            e.exit_code()
+        }
+    }
+}
+
+fn do_run(path: &Path, force: bool) -> Result<i32, FontError> {
+    let content = std::fs::read_to_string(path).map_err(|e| FontError::io(path, e))?;
+    let config = content
+        .parse::<toml::Table>()
+        .map_err(|e| FontError::Usage(e.to_string()))?;
+    let usage = |e: &str| Err(FontError::Usage(e.to_string()));
+    if let Some(key) = config.keys().find(|k| *k != "defaults" && *k != "job") {
+        return usage(&format!("unknown key {key}, expect defaults or job"));
+    }
+    let defaults = match config.get("defaults") {
+        Some(toml::Value::Table(t)) => t.clone(),
+        Some(_) => return usage("defaults must be a table"),
+        None => toml::Table::new(),
+    };
+    let jobs = match config.get("job") {
+        Some(toml::Value::Array(jobs)) => jobs.clone(),
+        Some(_) => return usage("job must be an array of tables, use [[job]]"),
+        None => return usage("no [[job]] found"),
+    };
+
+    // 相对路径都相对配置文件所在目录
+    let base = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let cache_path = base.join(CACHE_FILE);
+    let mut cache = std::fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|c| c.parse::<toml::Table>().ok())
+        .unwrap_or_default();
+
+    let mut code = 0;
+    for (index, job) in jobs.iter().enumerate() {
+        let toml::Value::Table(job) = job else {
+            return usage(&format!("job {index} must be a table"));
+        };
+        let mut merged = defaults.clone();
+        merged.extend(job.clone());
+        let job = Job::parse(&merged, base).map_err(|e| job_error(&index.to_string(), e))?;
+        let name = job.name.clone().unwrap_or_else(|| index.to_string());
+
+        let hash = job.hash(&merged).map_err(|e| job_error(&name, e))?;
+        if !force && job.unchanged(cache.get(&name), &hash) {
+            println!("job {name}: unchanged, skipped");
+            continue;
+        }
+
+        println!("job {name}:");
+        let summaries = job.run();
+        for s in summaries.iter().filter(|s| !s.missing.is_empty()) {
+            eprintln!("{}:", s.input.display());
+            crate::print_missing(&s.missing);
+        }
+        batch::print_summary(&summaries);
+
+        if summaries.iter().all(|s| s.result.is_ok()) {
+            let mut entry = toml::Table::new();
+            entry.insert("hash".to_string(), toml::Value::String(hash));
+            entry.insert(
+                "outputs".to_string(),
+                toml::Value::Array(
+                    summaries
+                        .iter()
+                        .filter_map(|s| s.output.as_ref())
+                        .map(|o| toml::Value::String(o.display().to_string()))
+                        .collect(),
+                ),
+            );
+            cache.insert(name, toml::Value::Table(entry));
+        } else {
+            cache.remove(&name);
+            // 以第一个失败的字体决定退出码
+            if code == 0
+                && let Some(Err(e)) = summaries.iter().map(|s| &s.result).find(|r| r.is_err())
+            {
+                code = e.exit_code();
+            }
+        }
+    }
+    if let Err(e) = std::fs::write(&cache_path, cache.to_string()) {
+        eprintln!("write {} fail: {}", cache_path.display(), e);
+    }
+    Ok(code)
+}
+
+/// 在参数错误前加上任务名称，读取文件失败的错误中已经有路径
+fn job_error(job: &str, e: FontError) -> FontError {
+    match e {
+        FontError::Usage(e) => FontError::Usage(format!("job {job}: {e}")),
+        e => e,
+    }
+}
+
+/// 一个子集化任务
+struct Job {
+    name: Option<String>,
+    inputs: Vec<PathBuf>,
+    output: String,
+    face: Option<String>,
+    /// 用于检查缺失字符的文本
+    text: String,
+    /// 实际子集化的文本，包含范围和字符集
+    subset: String,
+    options: SubsetOptions,
+    strict: bool,
+    jobs: usize,
+}
+
+impl Job {
+    fn parse(job: &toml::Table, base: &Path) -> Result<Job, FontError> {
+        if let Some(key) = job.keys().find(|k| !KEYS.contains(&k.as_str())) {
+            return Err(FontError::Usage(format!("unknown key {key}")));
+        }
+        let text = read_text(job, base)?;
+        Job::build(job, base, text).map_err(FontError::Usage)
+    }
+
+    /// 由 text、files、corpus 以外的键创建任务
+    fn build(job: &toml::Table, base: &Path, text: String) -> Result<Job, String> {
+        let path = |p: &str| base.join(p).display().to_string();
+
+        let inputs = strings(job, "input")?
+            .iter()
+            .map(|i| path(i))
+            .collect::<Vec<_>>();
+        if inputs.is_empty() {
+            return Err("input is required".to_string());
+        }
+        let inputs = batch::expand_inputs(&inputs)?;
+        let output = path(&string(job, "output")?.ok_or("output is required")?);
+
+        let range_text = font_info::parse_unicodes(&strings(job, "unicodes")?.join(","))?
+            + &charset::chars_of(&strings(job, "charsets")?.join(","))?;
+        if text.is_empty() && range_text.is_empty() {
+            return Err("require text, files, corpus, unicodes or charsets".to_string());
+        }
+
+        let options = SubsetOptions {
+            format: string(job, "format")?
+                .map(|f| f.parse())
+                .transpose()?
+                .unwrap_or_default(),
+            profile: string(job, "profile")?
+                .map(|p| p.parse())
+                .transpose()?
+                .unwrap_or_default(),
+            keep_tables: font_info::parse_tables(&strings(job, "keep_tables")?.join(","))?,
+            drop_tables: font_info::parse_tables(&strings(job, "drop_tables")?.join(","))?,
+            features: font_info::parse_tables(&strings(job, "features")?.join(","))?,
+            family: string(job, "family")?,
+            seed: match (integer(job, "seed")?, boolean(job, "deterministic")?) {
+                (Some(seed), _) => NameSeed::Fixed(
+                    u32::try_from(seed).map_err(|_| format!("invalid seed {seed}"))?,
+                ),
+                (None, true) => NameSeed::Hash,
+                (None, false) => NameSeed::Time,
+            },
+        };
+        options.validate()?;
+
+        Ok(Job {
+            name: string(job, "name")?,
+            inputs,
+            output,
+            face: match job.get("face") {
+                Some(toml::Value::Integer(i)) => Some(i.to_string()),
+                _ => string(job, "face")?,
+            },
+            subset: format!("{text}{range_text}"),
+            text,
+            options,
+            strict: boolean(job, "strict")?,
+            jobs: match integer(job, "jobs")? {
+                Some(jobs) => usize::try_from(jobs).map_err(|_| format!("invalid jobs {jobs}"))?,
+                None => std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1),
+            },
+        })
+    }
+
+    /// 由配置、输入字体和字符计算的哈希，用于判断任务是否需要重新执行
+    fn hash(&self, job: &toml::Table) -> Result<String, FontError> {
+        let mut hash = fnv1a(FNV_OFFSET, job.to_string().as_bytes());
+        for input in self.inputs.iter() {
+            hash = fnv1a(hash, input.display().to_string().as_bytes());
+            let font = std::fs::read(input).map_err(|e| FontError::io(input, e))?;
+            hash = fnv1a(hash, &font);
+        }
+        hash = fnv1a(hash, self.subset.as_bytes());
+        Ok(format!("{hash:08x}"))
+    }
+
+    /// 哈希相同并且上次的输出文件都还在
+    fn unchanged(&self, cached: Option<&toml::Value>, hash: &str) -> bool {
+        // 随机名称每次都不同，不能跳过
+        if self.options.seed == NameSeed::Time && self.options.family.is_none() {
+            return false;
+        }
+        let Some(toml::Value::Table(cached)) = cached else {
+            return false;
+        };
+        let outputs = match cached.get("outputs") {
+            Some(toml::Value::Array(outputs)) => outputs,
+            _ => return false,
+        };
+        cached.get("hash").and_then(|h| h.as_str()) == Some(hash)
+            && outputs
+                .iter()
+                .all(|o| o.as_str().is_some_and(|o| Path::new(o).exists()))
+    }
+
+    fn run(&self) -> Vec<batch::Summary> {
+        Batch {
+            output: &self.output,
+            face: self.face.as_deref(),
+            text: &self.text,
+            subset: &self.subset,
+            strict: self.strict,
+            options: &self.options,
+            jobs: self.jobs,
+        }
+        .run(&self.inputs)
+    }
+}
+
+/// 读取 text、files 和 corpus 中的文本，相对路径相对 base
+fn read_text(job: &toml::Table, base: &Path) -> Result<String, FontError> {
+    let mut text = string(job, "text")
+        .map_err(FontError::Usage)?
+        .unwrap_or_default();
+    for file in strings(job, "files").map_err(FontError::Usage)? {
+        let file = base.join(file);
+        text.push_str(&std::fs::read_to_string(&file).map_err(|e| FontError::io(&file, e))?);
+    }
+    if let Some(dir) = string(job, "corpus").map_err(FontError::Usage)? {
+        let dir = base.join(dir);
+        let include = strings(job, "include").map_err(FontError::Usage)?;
+        let exclude = strings(job, "exclude").map_err(FontError::Usage)?;
+        text.push_str(
+            &corpus::collect(&dir, &include, &exclude).map_err(|e| FontError::io(&dir, e))?,
+        );
+    }
+    Ok(text)
+}
+
+fn string(job: &toml::Table, key: &str) -> Result<Option<String>, String> {
+    match job.get(key) {
+        None => Ok(None),
+        Some(toml::Value::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(format!("{key} must be a string")),
+    }
+}
+
+/// 字符串或者字符串数组
+fn strings(job: &toml::Table, key: &str) -> Result<Vec<String>, String> {
+    match job.get(key) {
+        None => Ok(Vec::new()),
+        Some(toml::Value::String(s)) => Ok(vec![s.clone()]),
+        Some(toml::Value::Array(values)) => values
+            .iter()
+            .map(|v| {
+                v.as_str()
+                    .map(str::to_string)
+                    .ok_or(format!("{key} must be an array of strings"))
+            })
+            .collect(),
+        Some(_) => Err(format!("{key} must be a string or an array of strings")),
+    }
+}
+
+fn integer(job: &toml::Table, key: &str) -> Result<Option<i64>, String> {
+    match job.get(key) {
+        None => Ok(None),
+        Some(toml::Value::Integer(i)) => Ok(Some(*i)),
+        Some(_) => Err(format!("{key} must be an integer")),
+    }
+}
+
+fn boolean(job: &toml::Table, key: &str) -> Result<bool, String> {
+    match job.get(key) {
+        None => Ok(false),
+        Some(toml::Value::Boolean(b)) => Ok(*b),
+        Some(_) => Err(format!("{key} must be a boolean")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fontview::font_info::{OutputFormat, Profile};
+
+    const BASE: &str = env!("CARGO_MANIFEST_DIR");
+
+    fn parse(content: &str) -> Result<Job, FontError> {
+        let job = content.parse::<toml::Table>().unwrap();
+        Job::parse(&job, Path::new(BASE))
+    }
+
+    #[test]
+    fn parse_job() {
+        let job = parse(
+            r#"
+            name = "web"
+            input = "tests/fonts/Cantarell-Regular.ttf"
+            output = "out/{family}/{stem}-{index}.{ext}"
+            face = 0
+            text = "abc"
+            unicodes = "U+0030-0031"
+            format = "woff2"
+            profile = "web"
+            seed = 7
+            jobs = 2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(job.name.as_deref(), Some("web"));
+        assert_eq!(job.inputs.len(), 1);
+        assert_eq!(job.face.as_deref(), Some("0"));
+        assert_eq!(job.text, "abc");
+        assert_eq!(job.subset, "abc01");
+        assert_eq!(job.options.format, OutputFormat::Woff2);
+        assert_eq!(job.options.profile, Profile::Web);
+        assert_eq!(job.options.seed, NameSeed::Fixed(7));
+        assert_eq!(job.jobs, 2);
+        assert!(!job.strict);
+
+        // 模板相对配置文件所在目录，占位符按输入字体展开
+        let data = std::fs::read(&job.inputs[0]).unwrap();
+        let font = font_info::load(&data).unwrap();
+        let output = batch::output_of(
+            &job.output,
+            false,
+            &job.inputs[0],
+            1,
+            &font.table_provider(0).unwrap(),
+            job.options.format,
+        );
+        assert_eq!(
+            output,
+            Path::new(BASE).join("out/Cantarell/Cantarell-Regular-1.woff2")
+        );
+    }
+
+    #[test]
+    fn parse_deterministic() {
+        let job = parse(
+            r#"
+            input = "tests/fonts/Cantarell-Regular.ttf"
+            output = "out"
+            text = "abc"
+            deterministic = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(job.options.seed, NameSeed::Hash);
+        assert_eq!(job.options.profile, Profile::default());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let font = r#"input = "tests/fonts/Cantarell-Regular.ttf""#;
+        let cases = [
+            (
+                format!("{font}\noutput = \"out\"\ntext = \"a\"\nfoo = 1"),
+                "unknown key foo",
+            ),
+            (
+                "output = \"out\"\ntext = \"a\"".to_string(),
+                "input is required",
+            ),
+            (format!("{font}\ntext = \"a\""), "output is required"),
+            (format!("{font}\noutput = \"out\""), "require text"),
+            (
+                format!("{font}\noutput = \"out\"\ntext = 1"),
+                "text must be a string",
+            ),
+            (
+                format!("{font}\noutput = \"out\"\ntext = \"a\"\nseed = -1"),
+                "invalid seed -1",
+            ),
+            (
+                format!("{font}\noutput = \"out\"\ntext = \"a\"\nkeep_tables = \"DSIG\""),
+                "table DSIG can not be kept",
+            ),
+        ];
+        for (content, expected) in cases {
+            let err = parse(&content).err().unwrap();
+            assert!(matches!(err, FontError::Usage(_)), "{err}");
+            assert!(err.to_string().contains(expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn missing_file_is_io_error() {
+        let err = parse(
+            r#"
+            input = "tests/fonts/Cantarell-Regular.ttf"
+            output = "out"
+            files = "tests/missing.txt"
+            "#,
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(&err, FontError::Io(path, _) if path.ends_with("tests/missing.txt")));
+        assert_eq!(err.exit_code(), 4);
+    }
+}
@@ -0,0 +1,625 @@
+//! 从项目源码中提取界面上实际显示的文本
+//!
+//! 按文件格式只保留需要渲染的内容：HTML 的文本节点，JSON/YAML/TOML 的字符串值，
+//! gettext PO 的译文以及 Fluent 的消息内容，其它文件使用全部文本
+
+use std::path::Path;
+
+use crate::font_info::BoxError;
+
+/// 默认排除的目录
+const DEFAULT_EXCLUDE: &[&str] = &["**/.git/**", "**/node_modules/**", "**/target/**"];
+
+/// 遍历目录，提取匹配 include 且不匹配 exclude 的文件中的文本
+///
+/// include 为空时包含所有文件；不包含 `/` 的规则只匹配文件名，否则匹配相对路径
+pub fn collect(dir: &Path, include: &[String], exclude: &[String]) -> Result<String, BoxError> {
+    let mut text = String::new();
+    for (relative, path) in files(dir)? {
+        let included = include.is_empty() || include.iter().any(|p| matches(p, &relative));
+        let excluded = exclude
+            .iter()
+            .map(String::as_str)
+            .chain(DEFAULT_EXCLUDE.iter().copied())
+            .any(|p| matches(p, &relative));
+        if !included || excluded {
+            continue;
+        }
+        // 跳过二进制文件
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            crate::s_info!("skip {}", path.display());
+            continue;
+        };
+        text.push_str(&extract(&path, &content));
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// 目录下的所有文件，返回用 `/` 分隔的相对路径和完整路径，按相对路径排序
+///
+/// 不进入指向目录的符号链接，避免链接成环时无限递归
+pub fn files(dir: &Path) -> Result<Vec<(String, std::path::PathBuf)>, BoxError> {
+    let mut files = Vec::new();
+    walk(dir, dir, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn walk(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<(String, std::path::PathBuf)>,
+) -> Result<(), BoxError> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        // file_type 不会跟随符号链接
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk(root, &path, files)?;
+        } else if file_type.is_symlink() && path.is_dir() {
+            continue;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let relative = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.push((relative, path));
+        }
+    }
+    Ok(())
+}
+
+/// 匹配 glob 规则，支持 `*`、`?` 和跨目录的 `**`
+pub fn matches(pattern: &str, relative: &str) -> bool {
+    let target = if pattern.contains('/') {
+        relative
+    } else {
+        relative.rsplit('/').next().unwrap_or(relative)
+    };
+    let pattern = pattern.trim_start_matches("./").as_bytes();
+    glob(pattern, target.as_bytes())
+}
+
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            // `**/` 可以匹配零个或多个目录
+            glob(rest, text)
+                || text
+                    .iter()
+                    .enumerate()
+                    .any(|(i, c)| *c == b'/' && glob(rest, &text[i + 1..]))
+        }
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|i| *i == 0 || text[i - 1] != b'/')
+            .any(|i| glob(rest, &text[i..])),
+        [b'?', rest @ ..] => text.first().is_some_and(|c| *c != b'/') && glob(rest, &text[1..]),
+        [p, rest @ ..] => text.first() == Some(p) && glob(rest, &text[1..]),
+    }
+}
+
+/// 根据后缀提取文件中需要显示的文本
+pub(crate) fn extract(path: &Path, content: &str) -> String {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "html" | "htm" | "xhtml" | "xml" | "svg" | "vue" => html_text(content),
+        "json" => match serde_json::from_str::<serde_json::Value>(content) {
+            Ok(value) => {
+                let mut text = String::new();
+                json_strings(&value, &mut text);
+                text
+            }
+            Err(_) => content.to_string(),
+        },
+        "toml" => match content.parse::<toml::Table>() {
+            Ok(table) => {
+                let mut text = String::new();
+                for value in table.values() {
+                    toml_strings(value, &mut text);
+                }
+                text
+            }
+            Err(_) => content.to_string(),
+        },
+        "yaml" | "yml" => yaml_strings(content),
+        "po" | "pot" => po_strings(content),
+        "ftl" => fluent_strings(content),
+        _ => content.to_string(),
+    }
+}
+
+fn json_strings(value: &serde_json::Value, text: &mut String) {
+    match value {
+        serde_json::Value::String(s) => {
+            text.push_str(s);
+            text.push('\n');
+        }
+        serde_json::Value::Array(values) => values.iter().for_each(|v| json_strings(v, text)),
+        serde_json::Value::Object(map) => map.values().for_each(|v| json_strings(v, text)),
+        _ => {}
+    }
+}
+
+fn toml_strings(value: &toml::Value, text: &mut String) {
+    match value {
+        toml::Value::String(s) => {
+            text.push_str(s);
+            text.push('\n');
+        }
+        toml::Value::Array(values) => values.iter().for_each(|v| toml_strings(v, text)),
+        toml::Value::Table(table) => table.values().for_each(|v| toml_strings(v, text)),
+        _ => {}
+    }
+}
+
+/// HTML 中的文本节点和 alt、title、placeholder 等会显示的属性，跳过注释、script 和 style
+fn html_text(content: &str) -> String {
+    const ATTRIBUTES: &[&str] = &[
+        "alt",
+        "title",
+        "placeholder",
+        "label",
+        "aria-label",
+        "value",
+    ];
+    let mut text = String::new();
+    let mut rest = content;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&decode_entities(&rest[..start]));
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment
+                .find("-->")
+                .map(|end| &comment[end + 3..])
+                .unwrap_or("");
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .find(|s| !s.is_empty())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        for (attribute, value) in html_attributes(tag) {
+            if ATTRIBUTES.contains(&attribute.to_ascii_lowercase().as_str()) {
+                text.push('\n');
+                text.push_str(&decode_entities(value));
+            }
+        }
+        text.push('\n');
+        if (name == "script" || name == "style") && !tag.starts_with('/') && !tag.ends_with('/') {
+            let close = format!("</{name}");
+            rest = rest
+                .to_ascii_lowercase()
+                .find(&close)
+                .map(|i| &rest[i..])
+                .unwrap_or("");
+        }
+    }
+    text.push_str(&decode_entities(rest));
+    text
+}
+
+/// 解析标签中带引号的属性
+fn html_attributes(tag: &str) -> Vec<(&str, &str)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq]
+            .trim_end()
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or_default();
+        let value = rest[eq + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            rest = &rest[eq + 1..];
+            continue;
+        };
+        let value = &value[1..];
+        let end = value.find(quote).unwrap_or(value.len());
+        attributes.push((name, &value[..end]));
+        rest = value.get(end + 1..).unwrap_or_default();
+    }
+    attributes
+}
+
+/// 解码 HTML 实体，支持数字实体和常用的命名实体
+pub(crate) fn decode_entities(s: &str) -> String {
+    const NAMED: &[(&str, char)] = &[
+        ("amp", '&'),
+        ("lt", '<'),
+        ("gt", '>'),
+        ("quot", '"'),
+        ("apos", '\''),
+        ("nbsp", '\u{a0}'),
+        ("copy", '©'),
+        ("reg", '®'),
+        ("trade", '™'),
+        ("hellip", '…'),
+        ("mdash", '—'),
+        ("ndash", '–'),
+        ("lsquo", '‘'),
+        ("rsquo", '’'),
+        ("ldquo", '“'),
+        ("rdquo", '”'),
+        ("laquo", '«'),
+        ("raquo", '»'),
+        ("middot", '·'),
+        ("bull", '•'),
+        ("deg", '°'),
+        ("times", '×'),
+        ("divide", '÷'),
+        ("yen", '¥'),
+        ("euro", '€'),
+        ("pound", '£'),
+    ];
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 32)
+            .and_then(|end| {
+                let entity = &rest[1..end + 1];
+                let c = if let Some(hex) = entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                {
+                    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse().ok().and_then(char::from_u32)
+                } else {
+                    NAMED.iter().find(|(n, _)| *n == entity).map(|(_, c)| *c)
+                };
+                c.map(|c| (c, end + 2))
+            });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// YAML 中的标量值，包括 `|`、`>` 块标量，忽略键和注释
+fn yaml_strings(content: &str) -> String {
+    let mut text = String::new();
+    // 块标量的缩进，None 表示不在块标量中
+    let mut block: Option<usize> = None;
+    for line in content.lines() {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = line.trim();
+        if let Some(block_indent) = block {
+            if trimmed.is_empty() || indent > block_indent {
+                text.push_str(trimmed);
+                text.push('\n');
+                continue;
+            }
+            block = None;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed == "---" {
+            continue;
+        }
+        let item = trimmed.strip_prefix("- ").unwrap_or(trimmed);
+        let value = match yaml_key_end(item) {
+            Some(colon) => item[colon + 1..].trim(),
+            None => item,
+        };
+        if value.starts_with('|') || value.starts_with('>') {
+            block = Some(indent);
+            continue;
+        }
+        text.push_str(&yaml_scalar(value));
+        text.push('\n');
+    }
+    text
+}
+
+/// 查找键后面的冒号，引号中的冒号不算
+fn yaml_key_end(item: &str) -> Option<usize> {
+    if item.starts_with('"') || item.starts_with('\'') {
+        let quote = item.chars().next()?;
+        let end = item[1..].find(quote)? + 1;
+        return item[end + 1..].starts_with(':').then_some(end + 1);
+    }
+    item.char_indices()
+        .find(|(i, c)| *c == ':' && item[i + 1..].chars().next().is_none_or(char::is_whitespace))
+        .map(|(i, _)| i)
+}
+
+fn yaml_scalar(value: &str) -> String {
+    if let Some(quoted) = value.strip_prefix('"') {
+        let end = quoted.rfind('"').unwrap_or(quoted.len());
+        return unescape(&quoted[..end]);
+    }
+    if let Some(quoted) = value.strip_prefix('\'') {
+        let end = quoted.rfind('\'').unwrap_or(quoted.len());
+        return quoted[..end].replace("''", "'");
+    }
+    // 去掉行尾注释
+    match value.find(" #") {
+        Some(comment) => value[..comment].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// gettext PO 文件的译文，未翻译的条目使用原文
+fn po_strings(content: &str) -> String {
+    let mut text = String::new();
+    let mut msgid = String::new();
+    let mut msgstr = String::new();
+    // 当前续行属于 msgid 还是 msgstr
+    let mut in_msgstr = false;
+    let flush = |msgid: &mut String, msgstr: &mut String, text: &mut String| {
+        // msgid 为空的是 PO 文件头，msgstr 是元数据而不是界面文本
+        if !msgid.is_empty() {
+            text.push_str(if msgstr.is_empty() { msgid } else { msgstr });
+            text.push('\n');
+        }
+        msgid.clear();
+        msgstr.clear();
+    };
+    for line in content.lines().map(str::trim) {
+        if line.starts_with("msgid ") || line.starts_with("msgctxt ") {
+            if in_msgstr {
+                flush(&mut msgid, &mut msgstr, &mut text);
+            }
+            in_msgstr = false;
+            if let Some(rest) = line.strip_prefix("msgid ") {
+                msgid.push_str(&po_quoted(rest));
+            }
+        } else if line.starts_with("msgid_plural ") {
+            continue;
+        } else if line.starts_with("msgstr") {
+            in_msgstr = true;
+            if let Some((_, rest)) = line.split_once(' ') {
+                msgstr.push_str(&po_quoted(rest));
+            }
+        } else if line.starts_with('"') {
+            if in_msgstr {
+                msgstr.push_str(&po_quoted(line));
+            } else {
+                msgid.push_str(&po_quoted(line));
+            }
+        }
+    }
+    flush(&mut msgid, &mut msgstr, &mut text);
+    text
+}
+
+fn po_quoted(s: &str) -> String {
+    let s = s.trim();
+    let s = s.strip_prefix('"').unwrap_or(s);
+    let s = s.strip_suffix('"').unwrap_or(s);
+    unescape(s)
+}
+
+/// Fluent 消息和属性的值，去掉 `{ $var }` 这类占位，保留其中的字符串字面量和选择分支
+fn fluent_strings(content: &str) -> String {
+    let mut text = String::new();
+    for line in content.lines() {
+        let trimmed = line.trim();
+        // 单独一行的 `}` 是多行选择表达式的结尾
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed == "}" {
+            continue;
+        }
+        let value = if line.starts_with(char::is_whitespace) {
+            // 续行、属性或选择分支
+            let value = trimmed
+                .strip_prefix('.')
+                .and_then(|attr| attr.split_once('=').map(|(_, v)| v))
+                .unwrap_or(trimmed);
+            let value = value.strip_prefix('*').unwrap_or(value);
+            match value.strip_prefix('[') {
+                Some(variant) => variant.split_once(']').map(|(_, v)| v).unwrap_or_default(),
+                None => value,
+            }
+        } else {
+            match trimmed.split_once('=') {
+                Some((_, value)) => value,
+                None => continue,
+            }
+        };
+        text.push_str(&fluent_placeables(value.trim()));
+        text.push('\n');
+    }
+    text
+}
+
+fn fluent_placeables(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|e| start + e)
+            .unwrap_or(rest.len());
+        let placeable = &rest[start + 1..end];
+        // 字符串字面量会显示出来
+        let mut parts = placeable.split('"');
+        parts.next();
+        for (i, part) in parts.enumerate() {
+            if i % 2 == 0 {
+                out.push_str(&unescape(part));
+            }
+        }
+        rest = rest.get(end + 1..).unwrap_or_default();
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 处理 `\n`、`\"` 和 `\uXXXX` 转义
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('r') | Some('t') => out.push(' '),
+            Some('u') => {
+                let hex = chars.clone().take(4).collect::<String>();
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(u) if hex.len() == 4 => {
+                        out.push(u);
+                        chars.nth(3);
+                    }
+                    _ => out.push('u'),
+                }
+            }
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<&str> {
+        text.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn html_entities() {
+        assert_eq!(
+            decode_entities("a &amp; b &lt;&#x4E2D;&#25991;&gt; &copy; &unknown; & x"),
+            "a & b <中文> © &unknown; & x"
+        );
+    }
+
+    #[test]
+    fn html() {
+        let html = r#"<p title="提示">你好&nbsp;<b>世界</b></p>
+<!-- 注释 --><script>var s = "脚本";</script><img alt='图片'>"#;
+        assert_eq!(lines(&html_text(html)), ["提示", "你好", "世界", "图片"]);
+    }
+
+    #[test]
+    fn po() {
+        let po = r#"msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+"Language: zh_CN\n"
+
+#: src/main.rs:1
+msgid "Open"
+msgstr "打开"
+
+msgctxt "menu"
+msgid "Save"
+msgstr ""
+"保"
+"存"
+
+msgid "Untranslated"
+msgstr ""
+"#;
+        assert_eq!(lines(&po_strings(po)), ["打开", "保存", "Untranslated"]);
+    }
+
+    #[test]
+    fn fluent() {
+        let ftl = r#"# 注释
+hello = 你好，{ $name }！
+emails =
+    { $count ->
+        [one] 一封邮件
+       *[other] { $count } 封邮件
+    }
+login = 登录
+    .title = 点击{ "登录" }
+"#;
+        assert_eq!(
+            lines(&fluent_strings(ftl)),
+            ["你好，！", "一封邮件", "封邮件", "登录", "点击登录"]
+        );
+    }
+
+    #[test]
+    fn yaml() {
+        let yaml = r#"---
+# 注释
+title: 标题 # 行尾注释
+"quoted:key": "引号中"
+single: 'it''s'
+items:
+  - 第一项
+  - name: 第二项
+description: |
+  多行
+  文本
+url: http://example.com
+"#;
+        assert_eq!(
+            lines(&yaml_strings(yaml)),
+            [
+                "标题",
+                "引号中",
+                "it's",
+                "第一项",
+                "第二项",
+                "多行",
+                "文本",
+                "http://example.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn glob_patterns() {
+        assert!(matches("*.json", "locales/zh/app.json"));
+        assert!(matches("locales/**/*.ftl", "locales/zh/a/app.ftl"));
+        assert!(!matches("locales/*.ftl", "locales/zh/app.ftl"));
+        assert!(matches("app.?o", "po/app.po"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn walk_skips_symlinked_directories() {
+        let dir = std::env::temp_dir().join(format!("fontview-corpus-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/a.txt"), "a").unwrap();
+        // 指向上级目录的链接会形成环
+        std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+        std::os::unix::fs::symlink(dir.join("sub/a.txt"), dir.join("b.txt")).unwrap();
+
+        let found = files(&dir).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        let relative = found.iter().map(|(r, _)| r.as_str()).collect::<Vec<_>>();
+        assert_eq!(relative, ["b.txt", "sub/a.txt"]);
+    }
+}
@@ -0,0 +1,148 @@
+//! 回退链
+//!
+//! 按顺序为文本中的每个字符选择第一个包含它的字体，每个字体只保留自己负责的字符
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use fontview::font_info::{self, FontError, SubsetOptions};
+
+use crate::batch::{self, Summary};
+
+/// 回退链中一个字体的结果
+pub(crate) struct Coverage {
+    /// 负责的字符数量
+    pub chars: usize,
+    /// 没有负责任何字符时不输出字体，为 None
+    pub summary: Option<Summary>,
+}
+
+/// 回退链的参数
+pub(crate) struct Fallback<'a> {
+    pub output: &'a str,
+    pub face: Option<&'a str>,
+    /// 用于检查缺失字符的文本
+    pub text: &'a str,
+    /// 实际分配的文本，包含范围和字符集
+    pub subset: &'a str,
+    pub options: &'a SubsetOptions,
+}
+
+impl Fallback<'_> {
+    /// 按输入的顺序分配字符并子集化，返回每个字体的结果和所有字体都没有的字符
+    pub(crate) fn run(&self, inputs: &[PathBuf]) -> Result<(Vec<Coverage>, Vec<char>), FontError> {
+        let data = inputs
+            .iter()
+            .map(|p| std::fs::read(p).map_err(|e| FontError::io(p, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let fonts = data
+            .iter()
+            .map(|d| font_info::load(d))
+            .collect::<Result<Vec<_>, _>>()?;
+        let faces = data
+            .iter()
+            .map(|d| match self.face {
+                Some(face) => font_info::find_face(d, face)
+                    .ok_or_else(|| FontError::Usage(format!("face not found: {face}"))),
+                None => Ok(0),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let providers = fonts
+            .iter()
+            .zip(faces.iter())
+            .map(|(f, face)| f.table_provider(*face))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let plan = font_info::plan_fallback(&providers, self.subset)?;
+        let directory = !self.output.contains('{');
+        let mut claimed = HashSet::new();
+        let mut coverages = Vec::with_capacity(inputs.len());
+        for (index, text) in plan.texts.iter().enumerate() {
+            let chars = text.chars().filter(|c| !font_info::is_selector(*c)).count();
+            if chars == 0 {
+                coverages.push(Coverage {
+                    chars,
+                    summary: None,
+                });
+                continue;
+            }
+            let output = batch::output_of(
+                self.output,
+                directory,
+                &inputs[index],
+                index,
+                &providers[index],
+                self.options.format,
+            );
+            // 只输出计划分配给这个字体的字符，不加入占位符
+            let result = batch::claim(&mut claimed, &output)
+                .and_then(|_| create_parent(&output))
+                .and_then(|_| {
+                    font_info::subset_text_exact(&providers[index], text, &output, self.options)
+                });
+            coverages.push(Coverage {
+                chars,
+                summary: Some(Summary {
+                    input: inputs[index].clone(),
+                    face: faces[index],
+                    input_size: data[index].len() as u64,
+                    output: Some(output),
+                    missing: Vec::new(),
+                    result,
+                }),
+            });
+        }
+        // 范围和字符集内所有字体都没有的字符是预期的，不计入缺失字符
+        let missing = plan
+            .missing
+            .into_iter()
+            .filter(|c| self.text.contains(*c))
+            .collect();
+        Ok((coverages, missing))
+    }
+}
+
+fn create_parent(output: &Path) -> Result<(), FontError> {
+    match output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(parent) => std::fs::create_dir_all(parent).map_err(|e| FontError::write(parent, e)),
+        None => Ok(()),
+    }
+}
+
+/// 输出每个字体负责的字符数量和子集化结果
+pub(crate) fn print_coverage(inputs: &[PathBuf], coverages: &[Coverage], missing: &[char]) {
+    let rows = inputs
+        .iter()
+        .zip(coverages.iter())
+        .map(|(input, c)| {
+            let summary = c.summary.as_ref();
+            let report = summary.and_then(|s| s.result.as_ref().ok());
+            [
+                input.display().to_string(),
+                c.chars.to_string(),
+                summary
+                    .and_then(|s| s.output.as_ref())
+                    .map(|o| o.display().to_string())
+                    .unwrap_or_default(),
+                report.map(|r| r.family.clone()).unwrap_or_default(),
+                report
+                    .map(|r| format!("{:.1} KiB", r.size as f64 / 1024.0))
+                    .unwrap_or_default(),
+                match summary.map(|s| &s.result) {
+                    None => "skipped".to_string(),
+                    Some(Ok(_)) => "ok".to_string(),
+                    Some(Err(e)) => e.to_string(),
+                },
+            ]
+        })
+        .collect::<Vec<_>>();
+    batch::print_table(
+        &["FONT", "CHARACTERS", "OUTPUT", "FAMILY", "SIZE", "STATUS"],
+        &rows,
+    );
+    println!(
+        "{} characters, {} not covered by any font",
+        coverages.iter().map(|c| c.chars).sum::<usize>() + missing.len(),
+        missing.len()
+    );
+}
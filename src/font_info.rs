@@ -0,0 +1,1566 @@
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::str;
+
+use allsorts::gsub::{GlyphOrigin, RawGlyph, RawGlyphFlags};
+use allsorts::subset::SubsetProfile;
+
+use allsorts::binary::read::ReadScope;
+use allsorts::error::ParseError;
+use allsorts::font_data::FontData;
+use allsorts::tables::glyf::{GlyfTable, Glyph};
+use allsorts::tables::loca::LocaTable;
+use allsorts::tables::{
+    FontTableProvider, HeadTable, MaxpTable, NameTable, OffsetTable, OpenTypeData, TTCHeader,
+};
+use allsorts::tag::{self};
+use allsorts::woff::WoffFont;
+use allsorts::woff2::Woff2Font;
+
+pub type BoxError = Box<dyn std::error::Error>;
+
+pub use crate::sfnt::tag_display;
+pub use crate::uvs::is_selector;
+
+/// 子集输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// 根据输出文件后缀推断
+    #[default]
+    Auto,
+    /// 原始 TTF/OTF
+    Sfnt,
+    Woff,
+    Woff2,
+}
+
+impl OutputFormat {
+    /// 把 Auto 转换为具体格式，无法识别的后缀按 sfnt 输出
+    pub fn resolve(self, output_path: &Path) -> OutputFormat {
+        if self != OutputFormat::Auto {
+            return self;
+        }
+        match output_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("woff") => OutputFormat::Woff,
+            Some("woff2") => OutputFormat::Woff2,
+            _ => OutputFormat::Sfnt,
+        }
+    }
+
+    /// 输出文件后缀，sfnt 需要根据字体是否是 CFF 来区分
+    pub fn extension(self, cff: bool) -> &'static str {
+        match self {
+            OutputFormat::Woff => "woff",
+            OutputFormat::Woff2 => "woff2",
+            _ if cff => "otf",
+            _ => "ttf",
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(OutputFormat::Auto),
+            "ttf" | "otf" | "sfnt" => Ok(OutputFormat::Sfnt),
+            "woff" => Ok(OutputFormat::Woff),
+            "woff2" => Ok(OutputFormat::Woff2),
+            _ => Err(format!("unknown format {s}, expect ttf/otf/woff/woff2")),
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Auto => "Auto",
+            OutputFormat::Sfnt => "TTF/OTF",
+            OutputFormat::Woff => "WOFF",
+            OutputFormat::Woff2 => "WOFF2",
+        })
+    }
+}
+
+/// 子集化保留哪些表
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Profile {
+    /// 只保留渲染必须的表，不包含任何排版特性
+    #[default]
+    Minimal,
+    /// 额外保留 hinting、竖排度量和排版表
+    Web,
+}
+
+impl Profile {
+    /// 和 allsorts 对应 profile 保留的表一致，用于在其基础上增删
+    ///
+    /// web 同时也是能够保留的所有表
+    fn tables(self) -> &'static [u32] {
+        use crate::sfnt::tag;
+        const MINIMAL: &[u32] = &[
+            tag(b"cmap"),
+            tag(b"head"),
+            tag(b"hhea"),
+            tag(b"hmtx"),
+            tag(b"maxp"),
+            tag(b"name"),
+            tag(b"OS/2"),
+            tag(b"post"),
+        ];
+        const WEB: &[u32] = &[
+            tag(b"cmap"),
+            tag(b"head"),
+            tag(b"hhea"),
+            tag(b"hmtx"),
+            tag(b"maxp"),
+            tag(b"name"),
+            tag(b"OS/2"),
+            tag(b"post"),
+            tag(b"cvt "),
+            tag(b"fpgm"),
+            tag(b"prep"),
+            tag(b"gasp"),
+            tag(b"vhea"),
+            tag(b"vmtx"),
+            tag(b"VORG"),
+            tag(b"GDEF"),
+            tag(b"GSUB"),
+            tag(b"GPOS"),
+            tag(b"kern"),
+        ];
+        match self {
+            Profile::Minimal => MINIMAL,
+            Profile::Web => WEB,
+        }
+    }
+}
+
+impl std::str::FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "minimal" => Ok(Profile::Minimal),
+            "web" => Ok(Profile::Web),
+            _ => Err(format!("unknown profile {s}, expect minimal/web")),
+        }
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Profile::Minimal => "minimal",
+            Profile::Web => "web",
+        })
+    }
+}
+
+/// 子集化时不能删除的表
+const REQUIRED_TABLES: &[u32] = &[
+    crate::sfnt::tag(b"cmap"),
+    crate::sfnt::tag(b"head"),
+    crate::sfnt::tag(b"hhea"),
+    crate::sfnt::tag(b"hmtx"),
+    crate::sfnt::tag(b"maxp"),
+];
+
+/// 解析逗号分隔的表名，例如 `vhea,vmtx,VORG`，不足4个字符的用空格补齐
+pub fn parse_tables(s: &str) -> Result<Vec<u32>, String> {
+    s.split(',')
+        .map(|t| t.trim_matches(|c: char| c.is_ascii_whitespace() || c == '\''))
+        .filter(|t| !t.is_empty())
+        .map(|t| {
+            if t.len() > 4 || !t.is_ascii() {
+                return Err(format!("invalid table tag {t}"));
+            }
+            let mut b = *b"    ";
+            b[..t.len()].copy_from_slice(t.as_bytes());
+            Ok(u32::from_be_bytes(b))
+        })
+        .collect()
+}
+
+/// 随机名称的种子
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameSeed {
+    /// 使用当前时间，每次结果都不同
+    #[default]
+    Time,
+    /// 固定种子
+    Fixed(u32),
+    /// 由输入字体和字符集计算，输出完全可复现
+    Hash,
+}
+
+/// 子集化选项
+#[derive(Debug, Clone, Default)]
+pub struct SubsetOptions {
+    /// 输出格式
+    pub format: OutputFormat,
+    /// 保留哪些表
+    pub profile: Profile,
+    /// 在 profile 基础上额外保留的表
+    pub keep_tables: Vec<u32>,
+    /// 在 profile 基础上删除的表
+    pub drop_tables: Vec<u32>,
+    /// 子集字体的新名称，为空时随机生成
+    pub family: Option<String>,
+    /// 随机名称的种子
+    pub seed: NameSeed,
+    /// 计算 GSUB 闭包时使用的特性，为空时使用所有特性
+    pub features: Vec<u32>,
+}
+
+impl SubsetOptions {
+    /// 检查选项是否合法
+    pub fn validate(&self) -> Result<(), String> {
+        let keepable = Profile::Web.tables();
+        if let Some(t) = self.keep_tables.iter().find(|t| !keepable.contains(t)) {
+            return Err(format!(
+                "table {} can not be kept, supported tables: {}",
+                crate::sfnt::tag_display(*t),
+                keepable
+                    .iter()
+                    .map(|t| crate::sfnt::tag_display(*t).trim_end().to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ));
+        }
+        if let Some(t) = self
+            .drop_tables
+            .iter()
+            .find(|t| REQUIRED_TABLES.contains(t))
+        {
+            return Err(format!(
+                "table {} is required and can not be dropped",
+                crate::sfnt::tag_display(*t)
+            ));
+        }
+        Ok(())
+    }
+
+    fn subset_profile(&self) -> SubsetProfile {
+        if self.keep_tables.is_empty() && self.drop_tables.is_empty() {
+            return match self.profile {
+                Profile::Minimal => SubsetProfile::Minimal,
+                Profile::Web => SubsetProfile::Custom(self.tables()),
+            };
+        }
+        SubsetProfile::Custom(self.tables())
+    }
+
+    /// 子集中保留的表
+    fn tables(&self) -> Vec<u32> {
+        let mut tables = self.profile.tables().to_vec();
+        for t in self.keep_tables.iter() {
+            if !tables.contains(t) {
+                tables.push(*t);
+            }
+        }
+        tables.retain(|t| !self.drop_tables.contains(t));
+        tables
+    }
+}
+
+/// 子集化结果
+#[derive(Debug, Clone)]
+pub struct SubsetReport {
+    /// 子集字体的新名称
+    pub family: String,
+    /// 输出文件的格式，即文件后缀
+    pub format: &'static str,
+    /// 原字体的字形数量
+    pub original_glyphs: u16,
+    /// 子集的字形数量
+    pub glyphs: u16,
+    /// 文本中能够显示的字符数量，已去重，不包含自动加入的占位符
+    pub chars: usize,
+    /// 输出文件大小
+    pub size: u64,
+    /// 子集中每个表未压缩的大小
+    pub tables: Vec<(u32, usize)>,
+}
+
+/// 子集化错误
+#[derive(Debug)]
+pub enum FontError {
+    /// 参数或选项不合法
+    Usage(String),
+    /// 读取文件失败
+    Io(PathBuf, String),
+    /// 字体或文本无法解析
+    Parse(String),
+    /// 不支持的格式
+    Unsupported(String),
+    /// 文本在字体中没有任何字形
+    EmptyGlyphSet,
+    /// 严格模式下有缺失的字符
+    Missing(usize),
+    /// 写入或校验输出文件失败
+    Write(PathBuf, String),
+}
+
+impl FontError {
+    /// 命令行的退出码
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            FontError::Missing(_) => 2,
+            FontError::Usage(_) => 3,
+            FontError::Io(..) => 4,
+            FontError::Parse(_) => 5,
+            FontError::Unsupported(_) => 6,
+            FontError::EmptyGlyphSet => 7,
+            FontError::Write(..) => 8,
+        }
+    }
+
+    /// 读取 path 失败
+    pub fn io(path: &Path, e: impl std::fmt::Display) -> FontError {
+        FontError::Io(path.to_path_buf(), e.to_string())
+    }
+
+    /// 写入 path 失败
+    pub fn write(path: &Path, e: impl std::fmt::Display) -> FontError {
+        FontError::Write(path.to_path_buf(), e.to_string())
+    }
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::Usage(e) => write!(f, "{e}"),
+            FontError::Io(path, e) => write!(f, "read {} fail: {e}", path.display()),
+            FontError::Parse(e) => write!(f, "parse font fail: {e}"),
+            FontError::Unsupported(e) => write!(f, "unsupported: {e}"),
+            FontError::EmptyGlyphSet => {
+                f.write_str("no glyphs left in font, the font has none of the characters")
+            }
+            FontError::Missing(count) => write!(f, "missing {count} characters"),
+            FontError::Write(path, e) => write!(f, "write {} fail: {e}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+impl From<ParseError> for FontError {
+    fn from(e: ParseError) -> Self {
+        FontError::Parse(e.to_string())
+    }
+}
+
+impl From<BoxError> for FontError {
+    fn from(e: BoxError) -> Self {
+        FontError::Parse(e.to_string())
+    }
+}
+
+impl From<allsorts::error::ReadWriteError> for FontError {
+    fn from(e: allsorts::error::ReadWriteError) -> Self {
+        FontError::Parse(e.to_string())
+    }
+}
+
+impl From<allsorts::subset::SubsetError> for FontError {
+    fn from(e: allsorts::subset::SubsetError) -> Self {
+        FontError::Parse(e.to_string())
+    }
+}
+
+///
+/// 字体子集化
+///
+pub fn subset_text<F: FontTableProvider>(
+    font_provider: &F,
+    text: &str,
+    output_path: &PathBuf,
+    options: &SubsetOptions,
+) -> Result<SubsetReport, FontError> {
+    do_subset_text(
+        font_provider,
+        &prepare_text(text),
+        text,
+        output_path,
+        options,
+    )
+    .inspect_err(|e| {
+        s_error!("subset fail {:?}", e);
+    })
+}
+
+///
+/// 字体子集化，只保留 text 中的字符，不加入占位符
+///
+/// 用于回退链等由调用者决定每个字体负责哪些字符的场景
+///
+pub fn subset_text_exact<F: FontTableProvider>(
+    font_provider: &F,
+    text: &str,
+    output_path: &PathBuf,
+    options: &SubsetOptions,
+) -> Result<SubsetReport, FontError> {
+    do_subset_text(
+        font_provider,
+        &remove_duplicate_chars(text),
+        text,
+        output_path,
+        options,
+    )
+    .inspect_err(|e| {
+        s_error!("subset fail {:?}", e);
+    })
+}
+
+///
+/// 子集化字体集合中的多个字体，并重新打包为一个 ttc
+///
+pub fn subset_collection(
+    font_file: &FontData,
+    faces: &[usize],
+    text: &str,
+    output_path: &PathBuf,
+    options: &SubsetOptions,
+) -> Result<Vec<String>, FontError> {
+    do_subset_collection(
+        font_file,
+        faces,
+        prepare_text(text).as_str(),
+        output_path,
+        options,
+    )
+    .inspect_err(|e| {
+        s_error!("subset collection fail {:?}", e);
+    })
+}
+
+///
+/// 按 unicode-range 把字体切分为多个分片，并生成包含所有分片的 CSS
+///
+/// text 为空时切分字体中的所有字符，否则只切分 text 中字体包含的字符；
+/// priority 中的字符按出现顺序排在最前面；CSS 中的 font-family 未指定 family 时使用原字体的名称
+///
+pub fn subset_chunks<F: FontTableProvider>(
+    font_provider: &F,
+    text: &str,
+    chunks: usize,
+    priority: &str,
+    output_path: &Path,
+    css_path: &Path,
+    options: &SubsetOptions,
+) -> Result<Vec<String>, FontError> {
+    do_subset_chunks(
+        font_provider,
+        text,
+        chunks,
+        priority,
+        output_path,
+        css_path,
+        options,
+    )
+    .inspect_err(|e| {
+        s_error!("subset chunks fail {:?}", e);
+    })
+}
+
+///
+/// 子集化并按 options.format 编码，不写入文件，Auto 按 TTF/OTF 输出
+///
+/// 返回编码后的字体和新名称
+///
+pub fn subset_bytes<F: FontTableProvider>(
+    font_provider: &F,
+    text: &str,
+    options: &SubsetOptions,
+) -> Result<(Vec<u8>, String), FontError> {
+    options.validate().map_err(FontError::Usage)?;
+    let (new_font, family) = subset_font(font_provider, &prepare_text(text), options)?;
+    Ok((encode(new_font, options.format)?, family))
+}
+
+/// 添加两个占位符，用于字符不存在时渲染，避免完全不渲染的空白；并去重
+fn prepare_text(text: &str) -> String {
+    remove_duplicate_chars(&format!("{text}?◻"))
+}
+
+/// 文本去重，变体选择符和前一个字符作为一个整体
+fn remove_duplicate_chars(input: &str) -> String {
+    let mut seen = std::collections::HashSet::new();
+    let mut result = String::new();
+
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut unit = c.to_string();
+        while let Some(selector) = chars.next_if(|n| crate::uvs::is_selector(*n)) {
+            unit.push(selector);
+        }
+        if !seen.contains(&unit) {
+            result.push_str(&unit);
+            seen.insert(unit);
+        }
+    }
+
+    result
+}
+
+/// 随机数算法
+fn lcg(seed: u32) -> u32 {
+    let a: u64 = 1664525;
+    let c: u64 = 1013904223;
+    let m: u64 = 1 << 32;
+    ((a * seed as u64 + c) % m) as u32
+}
+
+/// subset 是实际子集化的文本，text 是调用者给出的文本，用于统计字符数量
+fn do_subset_text<F: FontTableProvider>(
+    font_provider: &F,
+    subset: &str,
+    text: &str,
+    output_path: &PathBuf,
+    options: &SubsetOptions,
+) -> Result<SubsetReport, FontError> {
+    options.validate().map_err(FontError::Usage)?;
+    let (new_font, family) = subset_font(font_provider, subset, options)?;
+
+    let sfnt = crate::sfnt::Sfnt::parse(&new_font)?;
+    let glyphs = sfnt
+        .table(tag::MAXP)
+        .and_then(|maxp| crate::sfnt::read_u16(maxp, 4).ok())
+        .unwrap_or_default();
+    let tables = sfnt
+        .tables
+        .iter()
+        .map(|t| (t.tag, t.data.len()))
+        .collect::<Vec<_>>();
+    let original_glyphs = glyph_count(font_provider).unwrap_or_default();
+    // 只统计调用者给出的文本，不包含 prepare_text 加入的占位符
+    let text = remove_duplicate_chars(text);
+    let chars = text
+        .chars()
+        .zip(chars_to_glyphs(font_provider, &text)?)
+        .filter(|(c, g)| g.is_some() && !crate::uvs::is_selector(*c))
+        .count();
+    let format = options
+        .format
+        .resolve(output_path)
+        .extension(new_font.starts_with(b"OTTO"));
+    write_font(new_font, output_path, options.format)?;
+    Ok(SubsetReport {
+        family,
+        format,
+        original_glyphs,
+        glyphs,
+        chars,
+        size: std::fs::metadata(output_path)
+            .map_err(|e| FontError::write(output_path, e))?
+            .len(),
+        tables,
+    })
+}
+
+fn do_subset_collection(
+    font_file: &FontData,
+    faces: &[usize],
+    text: &str,
+    output_path: &PathBuf,
+    options: &SubsetOptions,
+) -> Result<Vec<String>, FontError> {
+    if options.format.resolve(output_path) != OutputFormat::Sfnt {
+        return Err(FontError::Unsupported(
+            "font collection can only be written as ttc/otc".to_string(),
+        ));
+    }
+    options.validate().map_err(FontError::Usage)?;
+    let mut fonts = Vec::with_capacity(faces.len());
+    let mut names = Vec::with_capacity(faces.len());
+    for index in faces {
+        let provider = font_file.table_provider(*index)?;
+        let (new_font, rep) = subset_font(&provider, text, options)?;
+        fonts.push(new_font);
+        names.push(rep);
+    }
+    let collection = crate::sfnt::build_collection(&fonts)?;
+    std::fs::write(output_path, &collection).map_err(|e| FontError::write(output_path, e))?;
+    verify_output(output_path).map_err(|e| FontError::write(output_path, e))?;
+    Ok(names)
+}
+
+fn do_subset_chunks<F: FontTableProvider>(
+    font_provider: &F,
+    text: &str,
+    chunks: usize,
+    priority: &str,
+    output_path: &Path,
+    css_path: &Path,
+    options: &SubsetOptions,
+) -> Result<Vec<String>, FontError> {
+    options.validate().map_err(FontError::Usage)?;
+    if chunks == 0 {
+        return Err(FontError::Usage(
+            "chunks must be greater than 0".to_string(),
+        ));
+    }
+    let mut covered = covered_chars(font_provider)?;
+    if !text.is_empty() {
+        let wanted = text.chars().collect::<std::collections::HashSet<_>>();
+        covered.retain(|c| wanted.contains(c));
+    }
+    let slices = crate::chunk::split(&crate::chunk::order(&covered, priority), chunks);
+
+    // CSS 中的 font-family 未指定时使用原字体的名称，和分片内部的随机名称无关
+    let css_family = match options.family.as_deref().map(str::trim) {
+        Some(family) if !family.is_empty() => family.to_string(),
+        _ => names(font_provider)?.family,
+    };
+    // 所有分片使用同一个名称，未指定时沿用第一个分片生成的名称
+    let mut options = options.clone();
+    let mut faces = Vec::with_capacity(slices.len());
+    let mut paths = Vec::with_capacity(slices.len());
+    for (index, slice) in slices.iter().enumerate() {
+        let path = face_output_path(output_path, index);
+        // 分片只包含自己 unicode-range 中的字符，不加入占位符
+        let (new_font, family) = subset_font(font_provider, &String::from_iter(slice), &options)?;
+        options.family.get_or_insert(family);
+        let format = match options.format.resolve(&path) {
+            OutputFormat::Woff => "woff",
+            OutputFormat::Woff2 => "woff2",
+            _ if new_font.starts_with(b"OTTO") => "opentype",
+            _ => "truetype",
+        };
+        write_font(new_font, &path, options.format)?;
+        faces.push(crate::chunk::FontFace {
+            url: path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            format,
+            unicode_range: crate::chunk::unicode_range(slice),
+        });
+        paths.push(path.display().to_string());
+    }
+
+    // 从 OS/2 表读取字重和是否为斜体
+    let (weight, italic) = match font_provider.read_table_data(tag::OS_2) {
+        Ok(os2) => (
+            crate::sfnt::read_u16(&os2, 4).unwrap_or(400),
+            crate::sfnt::read_u16(&os2, 62).is_ok_and(|s| s & 1 != 0),
+        ),
+        Err(_) => (400, false),
+    };
+    // 原字体没有名称时使用分片的名称
+    let family = if css_family.is_empty() {
+        options.family.unwrap_or_default()
+    } else {
+        css_family
+    };
+    std::fs::write(css_path, crate::chunk::css(&family, weight, italic, &faces))
+        .map_err(|e| FontError::write(css_path, e))?;
+    Ok(paths)
+}
+
+/// 字体 cmap 中包含的所有字符
+fn covered_chars<F: FontTableProvider>(font_provider: &F) -> Result<Vec<char>, BoxError> {
+    let cmap_data = font_provider.read_table_data(allsorts::tag::CMAP)?;
+    let cmap = allsorts::binary::read::ReadScope::new(&cmap_data)
+        .read::<allsorts::tables::cmap::Cmap>()?;
+    let (_, cmap_subtable) = allsorts::font::read_cmap_subtable(&cmap)?.ok_or("fail")?;
+    let mut chars = Vec::new();
+    cmap_subtable.mappings_fn(|code, glyph| {
+        if glyph != 0
+            && let Some(ch) = char::from_u32(code)
+            && !ch.is_control()
+        {
+            chars.push(ch);
+        }
+    })?;
+    chars.sort_unstable();
+    chars.dedup();
+    Ok(chars)
+}
+
+/// 子集化并修改名称，返回 sfnt 字节和新名称
+fn subset_font<F: FontTableProvider>(
+    font_provider: &F,
+    text: &str,
+    options: &SubsetOptions,
+) -> Result<(Vec<u8>, String), FontError> {
+    // Work out the glyphs we want to keep from the text
+    let mut glyphs = chars_to_glyphs(font_provider, text)?;
+    let notdef = RawGlyph {
+        unicodes: allsorts::tinyvec::tiny_vec![],
+        glyph_index: 0,
+        liga_component_pos: 0,
+        glyph_origin: GlyphOrigin::Direct,
+        flags: RawGlyphFlags::empty(),
+        variation: None,
+        extra_data: (),
+    };
+    glyphs.insert(0, Some(notdef));
+
+    let mut glyphs: Vec<RawGlyph<()>> = glyphs.into_iter().flatten().collect();
+    glyphs.sort_by_key(|g| g.glyph_index);
+    let mut glyph_ids = glyphs
+        .iter()
+        .map(|glyph| glyph.glyph_index)
+        .collect::<Vec<_>>();
+    glyph_ids.dedup();
+    // 只剩下 .notdef
+    if glyph_ids.len() <= 1 {
+        return Err(FontError::EmptyGlyphSet);
+    }
+    // 保留 GSUB 时加入连字、备选等替换能够得到的字形
+    if options.tables().contains(&tag::GSUB)
+        && let Ok(gsub) = font_provider.read_table_data(tag::GSUB)
+    {
+        let mut closure = glyph_ids.iter().copied().collect();
+        crate::gsub::closure(&gsub, &mut closure, &options.features)?;
+        glyph_ids = closure.into_iter().collect();
+    }
+    let glyph_ids = with_components(font_provider, &glyph_ids)?;
+
+    s_info!("Number of glyphs in new font: {}", glyph_ids.len());
+
+    // Subset
+    let new_font = allsorts::subset::subset(
+        font_provider,
+        &glyph_ids,
+        &options.subset_profile(),
+        allsorts::subset::CmapTarget::Unrestricted,
+    )?;
+
+    // 修改name
+    let seed = match options.seed {
+        NameSeed::Time => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u32)
+            .unwrap_or_default(),
+        NameSeed::Fixed(seed) => seed,
+        // 由子集结果和字符集决定，相同输入得到相同名称
+        NameSeed::Hash => fnv1a(fnv1a(FNV_OFFSET, &new_font), text.as_bytes()),
+    };
+    let generated = random_name(seed);
+    let family = options
+        .family
+        .as_deref()
+        .map(str::trim)
+        .filter(|f| !f.is_empty())
+        .unwrap_or(generated.as_str())
+        .to_string();
+    let mut sfnt = crate::sfnt::Sfnt::parse(&new_font)?;
+
+    add_tables(font_provider, &mut sfnt, &glyph_ids, &options.tables())?;
+
+    // allsorts 不会输出 format 14 子表，需要把仍然有效的变体序列写回 cmap
+    let sequences = crate::uvs::Sequences::parse(&font_provider.read_table_data(tag::CMAP)?)?;
+    if !sequences.is_empty()
+        && let Some(cmap) = sfnt.table(tag::CMAP)
+    {
+        let glyph_map = glyph_ids
+            .iter()
+            .enumerate()
+            .map(|(new, old)| (*old, new as u16))
+            .collect();
+        let chars = text.chars().collect::<Vec<_>>();
+        let cmap = crate::uvs::with_sequences(cmap, &sequences.retain(&chars, &glyph_map))?;
+        sfnt.set_table(tag::CMAP, cmap);
+    }
+
+    if let Some(name_table) = sfnt.table(tag::NAME) {
+        let name = crate::rename::NewName {
+            family: &family,
+            // 不包含任何 ASCII 字母数字的名称无法用作 PostScript 名称
+            postscript_family: if family.chars().any(|c| c.is_ascii_alphanumeric()) {
+                &family
+            } else {
+                &generated
+            },
+        };
+        let renamed = crate::rename::rename(name_table, &name)?;
+        sfnt.set_table(tag::NAME, renamed);
+    }
+
+    Ok((sfnt.to_bytes(), family))
+}
+
+/// 加入复合字形引用的字形
+///
+/// 顺序和 allsorts 子集化时一致，返回值的下标即子集中的字形编号
+fn with_components<F: FontTableProvider>(
+    provider: &F,
+    glyph_ids: &[u16],
+) -> Result<Vec<u16>, FontError> {
+    let mut glyph_ids = glyph_ids.to_vec();
+    if !provider.has_table(tag::GLYF) {
+        return Ok(glyph_ids);
+    }
+    let head = ReadScope::new(&provider.read_table_data(tag::HEAD)?).read::<HeadTable>()?;
+    let maxp = ReadScope::new(&provider.read_table_data(tag::MAXP)?).read::<MaxpTable>()?;
+    let loca_data = provider.read_table_data(tag::LOCA)?;
+    let loca = ReadScope::new(&loca_data)
+        .read_dep::<LocaTable<'_>>((maxp.num_glyphs, head.index_to_loc_format))?;
+    let glyf_data = provider.read_table_data(tag::GLYF)?;
+    let mut glyf = ReadScope::new(&glyf_data).read_dep::<GlyfTable<'_>>(&loca)?;
+    let mut i = 0;
+    while i < glyph_ids.len() {
+        // 和 allsorts 一样只解析复合字形
+        let composite = glyf
+            .records()
+            .get(usize::from(glyph_ids[i]))
+            .is_some_and(|r| r.is_composite());
+        if composite && let Glyph::Composite(composite) = glyf.get_parsed_glyph(glyph_ids[i])? {
+            for component in composite.glyphs.iter() {
+                if !glyph_ids.contains(&component.glyph_index) {
+                    glyph_ids.push(component.glyph_index);
+                }
+            }
+        }
+        i += 1;
+    }
+    Ok(glyph_ids)
+}
+
+/// 写回 allsorts 不会输出的表，glyph_ids 的下标即子集中的字形编号
+fn add_tables<F: FontTableProvider>(
+    provider: &F,
+    sfnt: &mut crate::sfnt::Sfnt<'_>,
+    glyph_ids: &[u16],
+    tables: &[u32],
+) -> Result<(), FontError> {
+    let table = |t: u32| -> Result<Option<Vec<u8>>, FontError> {
+        if !tables.contains(&t) {
+            return Ok(None);
+        }
+        Ok(provider.table_data(t)?.map(|d| d.into_owned()))
+    };
+    // gasp 不引用字形，可以直接复制
+    if let Some(gasp) = table(tag::GASP)? {
+        sfnt.set_table(tag::GASP, gasp);
+    }
+    if let (Some(vhea), Some(vmtx)) = (table(tag::VHEA)?, table(tag::VMTX)?) {
+        let (vhea, vmtx) = crate::vertical::subset_vmtx(&vhea, &vmtx, glyph_ids)?;
+        sfnt.set_table(tag::VHEA, vhea);
+        sfnt.set_table(tag::VMTX, vmtx);
+    }
+    if let Some(vorg) = table(tag::VORG)? {
+        sfnt.set_table(tag::VORG, crate::vertical::subset_vorg(&vorg, glyph_ids)?);
+    }
+
+    let glyph_map = crate::layout::GlyphMap::new(glyph_ids);
+    if let Some(gdef) = table(tag::GDEF)? {
+        sfnt.set_table(tag::GDEF, crate::layout::subset_gdef(&gdef, &glyph_map)?);
+    }
+    if let Some(gsub) = table(tag::GSUB)? {
+        sfnt.set_table(tag::GSUB, crate::layout::subset_gsub(&gsub, &glyph_map)?);
+    }
+    if let Some(gpos) = table(tag::GPOS)? {
+        sfnt.set_table(tag::GPOS, crate::layout::subset_gpos(&gpos, &glyph_map)?);
+    }
+    if let Some(kern) = table(tag::KERN)?
+        && let Some(kern) = crate::layout::subset_kern(&kern, &glyph_map)?
+    {
+        sfnt.set_table(tag::KERN, kern);
+    }
+    Ok(())
+}
+
+/// 随机生成字体名称
+fn random_name(mut seed: u32) -> String {
+    let v = b"QWERTYUIOPASDFGHJKLMNBVCXZ";
+
+    (0..12)
+        .map(|_| {
+            seed = lcg(seed);
+            v[(seed % v.len() as u32) as usize] as char
+        })
+        .collect()
+}
+
+/// FNV-1a 哈希的初始值
+pub const FNV_OFFSET: u32 = 0x811c9dc5;
+
+/// FNV-1a 哈希，可以在上一次结果的基础上继续计算
+pub fn fnv1a(hash: u32, data: &[u8]) -> u32 {
+    data.iter()
+        .fold(hash, |h, b| (h ^ u32::from(*b)).wrapping_mul(0x01000193))
+}
+
+/// 按指定格式编码后写入文件
+fn write_font(
+    new_font: Vec<u8>,
+    output_path: &PathBuf,
+    format: OutputFormat,
+) -> Result<(), FontError> {
+    let new_font = encode(new_font, format.resolve(output_path))
+        .map_err(|e| FontError::write(output_path, e))?;
+
+    // Write out the new font
+    std::fs::write(output_path, &new_font).map_err(|e| FontError::write(output_path, e))?;
+    verify_output(output_path).map_err(|e| FontError::write(output_path, e))
+}
+
+/// 把 sfnt 编码为指定格式
+fn encode(new_font: Vec<u8>, format: OutputFormat) -> Result<Vec<u8>, BoxError> {
+    match format {
+        OutputFormat::Woff => crate::woff::encode_woff(&new_font),
+        OutputFormat::Woff2 => crate::woff::encode_woff2(&new_font),
+        _ => Ok(new_font),
+    }
+}
+
+/// 重新读取写入的文件，确认校验和正确并且能被正常解析
+fn verify_output(output_path: &Path) -> Result<(), BoxError> {
+    let data = std::fs::read(output_path)?;
+    match data.get(0..4) {
+        Some(b"wOFF") => crate::woff::verify_woff(&data)?,
+        Some(b"wOF2") => crate::woff::verify_woff2(&data)?,
+        _ => crate::sfnt::verify(&data)?,
+    }
+    let font_file = ReadScope::new(&data).read::<FontData>()?;
+    for index in 0..face_count(&data) {
+        let provider = font_file.table_provider(index)?;
+        for t in [tag::HEAD, tag::HHEA, tag::MAXP] {
+            provider.read_table_data(t)?;
+        }
+        chars_to_glyphs(&provider, "")?;
+    }
+    s_info!("verify {} success", output_path.display());
+    Ok(())
+}
+
+/// 解析 unicode 范围，返回范围内的所有字符
+///
+/// 支持 `U+0041`、`U+0000-00FF`、`U+4E??` 以及不带 `U+` 的十六进制，
+/// 用逗号或空白分隔，`#` 之后的内容视为注释
+pub fn parse_unicodes(s: &str) -> Result<String, String> {
+    let mut text = String::new();
+    for item in s
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|item| !item.is_empty())
+    {
+        let err = || format!("invalid unicode range {item}");
+        let hex = |v: &str| {
+            let v = v
+                .strip_prefix("U+")
+                .or_else(|| v.strip_prefix("u+"))
+                .unwrap_or(v);
+            u32::from_str_radix(v, 16).map_err(|_| err())
+        };
+        let (start, end) = match item.split_once('-') {
+            Some((start, end)) => (hex(start)?, hex(end)?),
+            None if item.contains('?') => {
+                (hex(&item.replace('?', "0"))?, hex(&item.replace('?', "F"))?)
+            }
+            None => (hex(item)?, hex(item)?),
+        };
+        if start > end || end > 0x10FFFF {
+            return Err(err());
+        }
+        // char::from_u32 会跳过代理区
+        text.extend((start..=end).filter_map(char::from_u32));
+    }
+    Ok(text)
+}
+
+/// 字体中没有字形的字符，已去重，忽略换行等控制字符
+pub fn missing_chars<F: FontTableProvider>(
+    font_provider: &F,
+    text: &str,
+) -> Result<Vec<char>, BoxError> {
+    let text = remove_duplicate_chars(text)
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+    let glyphs = chars_to_glyphs(font_provider, &text)?;
+    Ok(text
+        .chars()
+        .zip(glyphs)
+        .filter(|(_, g)| g.is_none())
+        .map(|(c, _)| c)
+        .collect())
+}
+
+/// 回退链中每个字体负责显示的字符
+#[derive(Debug, Clone, Default)]
+pub struct FallbackPlan {
+    /// 和输入的字体一一对应
+    pub texts: Vec<String>,
+    /// 所有字体都没有的字符
+    pub missing: Vec<char>,
+}
+
+/// 按顺序把文本中的每个字符分配给第一个包含它的字体
+///
+/// 变体选择符跟随前一个字符分配
+pub fn plan_fallback<F: FontTableProvider>(
+    font_providers: &[F],
+    text: &str,
+) -> Result<FallbackPlan, FontError> {
+    let mut rest = remove_duplicate_chars(text)
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+    let mut texts = Vec::with_capacity(font_providers.len());
+    for font_provider in font_providers {
+        let glyphs = chars_to_glyphs(font_provider, &rest)?;
+        let mut covered = String::new();
+        let mut uncovered = String::new();
+        let mut found = false;
+        for (c, g) in rest.chars().zip(glyphs) {
+            if !crate::uvs::is_selector(c) {
+                found = g.is_some();
+            }
+            if found {
+                covered.push(c);
+            } else {
+                uncovered.push(c);
+            }
+        }
+        texts.push(covered);
+        rest = uncovered;
+    }
+    Ok(FallbackPlan {
+        texts,
+        missing: rest
+            .chars()
+            .filter(|c| !crate::uvs::is_selector(*c))
+            .collect(),
+    })
+}
+
+/// 文本中每个字符对应的字形编号，字体中没有的字符为 None
+///
+/// 变体选择符和前一个字符组成序列，选择符的位置是序列对应的字形
+pub fn text_to_glyphs<F: FontTableProvider>(
+    font_provider: &F,
+    text: &str,
+) -> Result<Vec<Option<u16>>, FontError> {
+    Ok(chars_to_glyphs(font_provider, text)?
+        .into_iter()
+        .map(|g| g.map(|g| g.glyph_index))
+        .collect())
+}
+
+fn chars_to_glyphs<F: FontTableProvider>(
+    font_provider: &F,
+    text: &str,
+) -> Result<Vec<Option<RawGlyph<()>>>, BoxError> {
+    let cmap_data = font_provider.read_table_data(allsorts::tag::CMAP)?;
+    let cmap = allsorts::binary::read::ReadScope::new(&cmap_data)
+        .read::<allsorts::tables::cmap::Cmap>()?;
+    let (_, cmap_subtable) = allsorts::font::read_cmap_subtable(&cmap)?.ok_or("fail")?;
+
+    let sequences = crate::uvs::Sequences::parse(&cmap_data)?;
+
+    // 变体选择符通过 format 14 子表和前一个字符一起查找，字体不支持该序列时视为缺失
+    let mut glyphs = Vec::with_capacity(text.len());
+    let mut base = None;
+    for ch in text.chars() {
+        if !crate::uvs::is_selector(ch) {
+            base = Some(ch);
+            glyphs.push(map(&cmap_subtable, ch, None)?);
+            continue;
+        }
+        let glyph = match base.and_then(|b| Some((b, sequences.lookup(b, ch)?))) {
+            Some((b, crate::uvs::Variant::Default)) => map(&cmap_subtable, b, None)?,
+            Some((b, crate::uvs::Variant::Glyph(glyph_index))) => Some(make(b, glyph_index, None)),
+            None => None,
+        };
+        glyphs.push(glyph);
+    }
+
+    Ok(glyphs)
+}
+fn map(
+    cmap_subtable: &allsorts::tables::cmap::CmapSubtable,
+    ch: char,
+    variation: Option<allsorts::unicode::VariationSelector>,
+) -> Result<Option<RawGlyph<()>>, allsorts::error::ParseError> {
+    match cmap_subtable.map_glyph(ch as u32) {
+        Ok(Some(glyph_index)) => Ok(Some(make(ch, glyph_index, variation))),
+        Ok(None) => Ok(None),
+        // format 4 等子表无法映射 BMP 以外的字符
+        Err(_) if u32::from(ch) > 0xffff => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+fn make(
+    ch: char,
+    glyph_index: u16,
+    variation: Option<allsorts::unicode::VariationSelector>,
+) -> RawGlyph<()> {
+    RawGlyph {
+        unicodes: allsorts::tinyvec::tiny_vec![[char; 1] => ch],
+        glyph_index,
+        liga_component_pos: 0,
+        glyph_origin: GlyphOrigin::Char(ch),
+        flags: RawGlyphFlags::empty(),
+        variation,
+        extra_data: (),
+    }
+}
+/// 解析字体文件，支持 TTF/OTF、TTC/OTC、WOFF 和 WOFF2，通过 `table_provider(index)` 读取其中的字体
+pub fn load(data: &[u8]) -> Result<FontData<'_>, FontError> {
+    Ok(ReadScope::new(data).read::<FontData>()?)
+}
+
+/// 字体名称
+#[derive(Debug, Clone, Default)]
+pub struct FontNames {
+    /// 字体族名称，优先使用排版名称（name ID 16）
+    pub family: String,
+    /// 子族名称，例如 Bold，优先使用排版名称（name ID 17）
+    pub subfamily: String,
+}
+
+/// 读取字体的英文名称，没有子族名称时为 Regular
+pub fn names<F: FontTableProvider>(font_provider: &F) -> Result<FontNames, FontError> {
+    let name_table = font_provider.read_table_data(tag::NAME)?;
+    let (family, subfamily) = crate::rename::family_names(&name_table)?;
+    Ok(FontNames { family, subfamily })
+}
+
+/// maxp 中的字形数量
+pub fn glyph_count<F: FontTableProvider>(font_provider: &F) -> Result<u16, FontError> {
+    let maxp = font_provider.read_table_data(tag::MAXP)?;
+    Ok(crate::sfnt::read_u16(&maxp, 4)?)
+}
+
+/// 字体文件中包含的字体数量，ttc 和 woff2 集合会大于1
+pub fn face_count(data: &[u8]) -> usize {
+    let count = match data.get(0..4) {
+        Some(b"ttcf") => crate::sfnt::read_u32(data, 8).map(|v| v as usize),
+        Some(b"wOF2") => crate::woff::woff2_face_count(data),
+        _ => Ok(1),
+    };
+    count.unwrap_or(1).max(1)
+}
+
+/// 根据下标或者名称查找字体集合中的字体
+pub fn find_face(data: &[u8], key: &str) -> Option<usize> {
+    let count = face_count(data);
+    if let Ok(index) = key.parse::<usize>() {
+        return (index < count).then_some(index);
+    }
+    let names = (0..count).map(|i| dump(data, i)).collect::<Vec<_>>();
+    names
+        .iter()
+        .position(|n| n.eq_ignore_ascii_case(key))
+        .or_else(|| {
+            let key = key.to_lowercase();
+            names.iter().position(|n| n.to_lowercase().contains(&key))
+        })
+}
+
+/// 每个字体单独输出时的文件名，例如 out.ttf -> out-1.ttf
+pub fn face_output_path(output_path: &Path, index: usize) -> PathBuf {
+    let stem = output_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match output_path.extension() {
+        Some(ext) => format!("{stem}-{index}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{index}"),
+    };
+    output_path.with_file_name(name)
+}
+
+/// 字体的表和名称信息，用于界面展示
+pub fn dump(data: &[u8], index: usize) -> String {
+    match do_dump(data, index) {
+        Ok(v) => v,
+        Err(e) => {
+            s_error!("dump error {:?}", e);
+            String::new()
+        }
+    }
+}
+fn do_dump(data: &[u8], index: usize) -> Result<String, BoxError> {
+    let scope = ReadScope::new(data);
+    let font_file = scope.read::<FontData>()?;
+
+    match &font_file {
+        FontData::OpenType(font_file) => match &font_file.data {
+            OpenTypeData::Single(ttf) => dump_ttf(&font_file.scope, ttf),
+            OpenTypeData::Collection(ttc) => dump_ttc(&font_file.scope, ttc, index),
+        },
+        FontData::Woff(woff_file) => dump_woff(woff_file),
+        FontData::Woff2(woff_file) => dump_woff2(woff_file, index),
+    }
+}
+
+fn dump_ttc<'a>(
+    scope: &ReadScope<'a>,
+    ttc: &TTCHeader<'a>,
+    index: usize,
+) -> Result<String, BoxError> {
+    if let Some(offset_table_offset) = (&ttc.offset_tables).into_iter().nth(index) {
+        let offset_table_offset = usize::try_from(offset_table_offset).map_err(ParseError::from)?;
+        let offset_table = scope.offset(offset_table_offset).read::<OffsetTable>()?;
+        return dump_ttf(scope, &offset_table);
+    }
+    Ok(String::new())
+}
+
+fn dump_ttf<'a>(scope: &ReadScope<'a>, ttf: &OffsetTable<'a>) -> Result<String, BoxError> {
+    if let Some(name_table_data) = ttf.read_table(scope, tag::NAME)? {
+        return dump_name_table(name_table_data.data());
+    }
+
+    Ok(String::new())
+}
+
+fn dump_woff(woff: &WoffFont<'_>) -> Result<String, BoxError> {
+    if let Some(entry) = woff
+        .table_directory
+        .iter()
+        .find(|entry| entry.tag == tag::NAME)
+    {
+        let table = entry.read_table(&woff.scope)?;
+        return dump_name_table(table.scope().data());
+    }
+
+    Ok(String::new())
+}
+
+fn dump_woff2<'a>(woff: &Woff2Font<'a>, index: usize) -> Result<String, BoxError> {
+    if let Some(table) = woff.read_table(tag::NAME, index)? {
+        s_info!();
+        return dump_name_table(table.scope().data());
+    }
+
+    Ok(String::new())
+}
+
+/// 第一条能够解码的完整名称记录
+fn dump_name_table(name_table: &[u8]) -> Result<String, BoxError> {
+    Ok(crate::rename::records(name_table)?
+        .into_iter()
+        .filter(|r| r.name_id == NameTable::FULL_FONT_NAME)
+        .find_map(|r| r.value)
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sfnt::{Sfnt, read_u16};
+
+    const FONT: &[u8] = include_bytes!("../tests/fonts/Cantarell-Regular.ttf");
+
+    /// 加入竖排度量表，每个字形的 topSideBearing 等于字形编号，VORG 只记录 `f`
+    fn with_vertical(data: &[u8]) -> Vec<u8> {
+        let font = load(data).unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let glyphs = glyph_count(&provider).unwrap();
+        let f = text_to_glyphs(&provider, "f").unwrap()[0].unwrap();
+
+        let mut vhea = vec![0; 36];
+        vhea[..4].copy_from_slice(&0x0001_1000u32.to_be_bytes());
+        vhea[34..].copy_from_slice(&2u16.to_be_bytes());
+        let mut vmtx = Vec::new();
+        for id in 0..glyphs {
+            if id < 2 {
+                vmtx.extend_from_slice(&1000u16.to_be_bytes());
+            }
+            vmtx.extend_from_slice(&id.to_be_bytes());
+        }
+        let vorg = [1u16, 0, 880, 1, f, 900]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect::<Vec<_>>();
+
+        let mut sfnt = Sfnt::parse(data).unwrap();
+        sfnt.set_table(tag::VHEA, vhea);
+        sfnt.set_table(tag::VMTX, vmtx);
+        sfnt.set_table(tag::VORG, vorg);
+        sfnt.to_bytes()
+    }
+
+    #[test]
+    fn web_profile_keeps_tables() {
+        let data = with_vertical(FONT);
+        let font = load(&data).unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let options = SubsetOptions {
+            profile: Profile::Web,
+            ..Default::default()
+        };
+        let (subset, _) = subset_bytes(&provider, "fontview", &options).unwrap();
+        let sfnt = Sfnt::parse(&subset).unwrap();
+        for t in [tag::GASP, tag::CVT, tag::VHEA, tag::VMTX, tag::VORG] {
+            assert!(sfnt.table(t).is_some(), "{} missing", tag_display(t));
+        }
+
+        let subset_font = load(&subset).unwrap();
+        let subset_provider = subset_font.table_provider(0).unwrap();
+        let glyphs = glyph_count(&subset_provider).unwrap();
+        let vhea = sfnt.table(tag::VHEA).unwrap();
+        let vmtx = sfnt.table(tag::VMTX).unwrap();
+        assert_eq!(read_u16(vhea, 34).unwrap(), glyphs);
+        assert_eq!(vmtx.len(), usize::from(glyphs) * 4);
+
+        // 子集中每个字形的 topSideBearing 仍然是原字体中的字形编号
+        let old = text_to_glyphs(&provider, "f").unwrap()[0].unwrap();
+        let new = text_to_glyphs(&subset_provider, "f").unwrap()[0].unwrap();
+        assert_eq!(read_u16(vmtx, usize::from(new) * 4 + 2).unwrap(), old);
+        let vorg = sfnt.table(tag::VORG).unwrap();
+        assert_eq!(read_u16(vorg, 6).unwrap(), 1);
+        assert_eq!(read_u16(vorg, 8).unwrap(), new);
+        assert_eq!(read_u16(vorg, 10).unwrap(), 900);
+    }
+
+    #[test]
+    fn minimal_profile_keeps_extra_tables() {
+        let font = load(FONT).unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let options = SubsetOptions {
+            keep_tables: vec![tag::GASP],
+            ..Default::default()
+        };
+        let (subset, _) = subset_bytes(&provider, "fontview", &options).unwrap();
+        let sfnt = Sfnt::parse(&subset).unwrap();
+        assert!(sfnt.table(tag::GASP).is_some());
+        assert!(sfnt.table(tag::CVT).is_none());
+    }
+
+    #[test]
+    fn parse_unicode_ranges() {
+        assert_eq!(parse_unicodes("U+0041").unwrap(), "A");
+        assert_eq!(parse_unicodes("u+41-43, 61").unwrap(), "ABCa");
+        assert_eq!(parse_unicodes("U+4E0?").unwrap().chars().count(), 16);
+        assert_eq!(parse_unicodes("41 # A\n# 注释\n42\t43").unwrap(), "ABC");
+        // 代理区不是字符
+        assert_eq!(parse_unicodes("D7FF-E000").unwrap(), "\u{d7ff}\u{e000}");
+        assert!(parse_unicodes("U+43-41").is_err());
+        assert!(parse_unicodes("110000").is_err());
+        assert!(parse_unicodes("U+XYZ").is_err());
+    }
+
+    #[test]
+    fn duplicate_chars_keep_sequences() {
+        assert_eq!(
+            remove_duplicate_chars("abab葛\u{e0100}葛葛\u{e0100}"),
+            "ab葛\u{e0100}葛"
+        );
+    }
+
+    #[test]
+    fn report_counts_only_requested_chars() {
+        let font = load(FONT).unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let output =
+            std::env::temp_dir().join(format!("fontview-report-{}.ttf", std::process::id()));
+        // 重复的字符只计一次，字体中没有的字符和占位符都不计入
+        let report = subset_text(&provider, "aab中", &output, &SubsetOptions::default()).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        assert_eq!(report.chars, 2);
+    }
+
+    #[test]
+    fn exact_subset_has_no_placeholders() {
+        let font = load(FONT).unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let output =
+            std::env::temp_dir().join(format!("fontview-exact-{}.ttf", std::process::id()));
+        subset_text_exact(&provider, "ab", &output, &SubsetOptions::default()).unwrap();
+        let data = std::fs::read(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        let subset = load(&data).unwrap();
+        let glyphs = text_to_glyphs(&subset.table_provider(0).unwrap(), "ab?").unwrap();
+        assert!(glyphs[..2].iter().all(|g| g.is_some_and(|g| g != 0)));
+        // 未映射的字符对应 .notdef
+        assert_eq!(glyphs[2].unwrap_or_default(), 0);
+    }
+
+    #[test]
+    fn chunks_only_contain_their_range() {
+        let font = load(FONT).unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let covered = covered_chars(&provider).unwrap();
+        assert!(covered.contains(&'a') && !covered.contains(&'中'));
+        assert!(covered.is_sorted());
+
+        let dir = std::env::temp_dir().join(format!("fontview-chunks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = subset_chunks(
+            &provider,
+            "abcxyz",
+            2,
+            "",
+            &dir.join("chunk.ttf"),
+            &dir.join("chunk.css"),
+            &SubsetOptions::default(),
+        )
+        .unwrap();
+        let chunks = paths
+            .iter()
+            .map(|p| {
+                let data = std::fs::read(p).unwrap();
+                covered_chars(&load(&data).unwrap().table_provider(0).unwrap()).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let css = std::fs::read_to_string(dir.join("chunk.css")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(chunks, [vec!['a', 'b', 'c'], vec!['x', 'y', 'z']]);
+        assert!(css.contains("U+61-63") && css.contains("U+78-7A"));
+    }
+
+    /// 加入 GSUB 和 GPOS：liga 把 `fi` 替换为 `Z`，kern 调整 `AV` 和 `AW` 的间距
+    fn with_layout(data: &[u8]) -> Vec<u8> {
+        let font = load(data).unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let glyph = |c: &str| text_to_glyphs(&provider, c).unwrap()[0].unwrap();
+        let (a, v, w) = (glyph("A"), glyph("V"), glyph("W"));
+        let (f, i, z) = (glyph("f"), glyph("i"), glyph("Z"));
+        let be = |values: &[u16]| {
+            values
+                .iter()
+                .flat_map(|v| v.to_be_bytes())
+                .collect::<Vec<_>>()
+        };
+        // 头部、只有 DFLT 的 ScriptList、只有一个特性的 FeatureList 和只有一个查找的 LookupList
+        let header = |feature: &[u8; 4], kind: u16| {
+            let feature = [
+                u16::from_be_bytes([feature[0], feature[1]]),
+                u16::from_be_bytes([feature[2], feature[3]]),
+            ];
+            #[rustfmt::skip]
+            let values = [
+                1, 0, 10, 30, 44,
+                1, 0x4446, 0x4C54, 8, 4, 0, 0, 0xFFFF, 1, 0,
+                1, feature[0], feature[1], 8, 0, 1, 0,
+                1, 4, kind, 0, 1, 8,
+            ];
+            be(&values)
+        };
+        let mut gsub = header(b"liga", 4);
+        gsub.extend(be(&[1, 8, 1, 14, 1, 1, f, 1, 4, z, 2, i]));
+        let mut gpos = header(b"kern", 2);
+        gpos.extend(be(&[
+            1,
+            12,
+            4,
+            0,
+            1,
+            18,
+            1,
+            1,
+            a,
+            2,
+            v,
+            (-80i16) as u16,
+            w,
+            (-60i16) as u16,
+        ]));
+
+        let mut sfnt = Sfnt::parse(data).unwrap();
+        sfnt.set_table(tag::GSUB, gsub);
+        sfnt.set_table(tag::GPOS, gpos);
+        sfnt.to_bytes()
+    }
+
+    /// 按默认特性排版，返回字形编号和前进宽度
+    fn shape(data: &[u8], text: &str) -> Vec<(u16, i32)> {
+        use allsorts::font::MatchingPresentation;
+        use allsorts::glyph_position::{GlyphLayout, TextDirection};
+        use allsorts::gsub::{FeatureMask, Features};
+
+        let font = load(data).unwrap();
+        let mut font = allsorts::Font::new(font.table_provider(0).unwrap()).unwrap();
+        let glyphs = font.map_glyphs(text, tag::LATN, MatchingPresentation::NotRequired);
+        let features = Features::Mask(FeatureMask::default());
+        let infos = font
+            .shape(glyphs, tag::LATN, None, &features, None, true)
+            .unwrap();
+        let positions = GlyphLayout::new(&mut font, &infos, TextDirection::LeftToRight, false)
+            .glyph_positions()
+            .unwrap();
+        infos
+            .iter()
+            .zip(positions)
+            .map(|(info, position)| (info.glyph.glyph_index, position.hori_advance))
+            .collect()
+    }
+
+    #[test]
+    fn web_profile_subsets_layout_tables() {
+        let data = with_layout(FONT);
+        let font = load(&data).unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let options = SubsetOptions {
+            profile: Profile::Web,
+            ..Default::default()
+        };
+        let (subset, _) = subset_bytes(&provider, "AVfiZ", &options).unwrap();
+        let sfnt = Sfnt::parse(&subset).unwrap();
+        for t in [tag::GDEF, tag::GSUB, tag::GPOS] {
+            assert!(sfnt.table(t).is_some(), "{} missing", tag_display(t));
+        }
+
+        // 字形编号改变后连字和字偶距仍然生效
+        let subset_font = load(&subset).unwrap();
+        let subset_provider = subset_font.table_provider(0).unwrap();
+        let z = text_to_glyphs(&subset_provider, "Z").unwrap()[0].unwrap();
+        assert_eq!(shape(&subset, "fi"), [(z, shape(&data, "Z")[0].1)]);
+        let advances = |data: &[u8]| shape(data, "AVfi").iter().map(|g| g.1).collect::<Vec<_>>();
+        assert_eq!(advances(&subset), advances(&data));
+        assert_ne!(shape(&subset, "AV")[0].1, shape(FONT, "AV")[0].1);
+
+        // 另一个字形不在子集中的字偶被删除
+        let gpos = sfnt.table(tag::GPOS).unwrap();
+        let lookup_list = usize::from(read_u16(gpos, 8).unwrap());
+        let lookup = lookup_list + usize::from(read_u16(gpos, lookup_list + 2).unwrap());
+        let subtable = lookup + usize::from(read_u16(gpos, lookup + 6).unwrap());
+        let pair_set = subtable + usize::from(read_u16(gpos, subtable + 10).unwrap());
+        assert_eq!(read_u16(gpos, pair_set).unwrap(), 1);
+    }
+
+    #[test]
+    fn gsub_closure_keeps_ligatures() {
+        let data = with_layout(FONT);
+        let font = load(&data).unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let z = text_to_glyphs(&provider, "Z").unwrap()[0].unwrap();
+        let options = SubsetOptions {
+            profile: Profile::Web,
+            ..Default::default()
+        };
+        // 文本中没有 `Z`，连字字形由闭包加入
+        let (subset, _) = subset_bytes(&provider, "fi", &options).unwrap();
+        let advance = shape(&data, "fi")[0].1;
+        assert_eq!(shape(&data, "fi"), [(z, advance)]);
+        let shaped = shape(&subset, "fi");
+        assert_eq!(shaped.len(), 1);
+        assert_ne!(shaped[0].0, 0);
+        assert_eq!(shaped[0].1, advance);
+
+        // 只使用其它特性时不会加入连字字形
+        let options = SubsetOptions {
+            features: vec![tag::KERN],
+            ..options
+        };
+        let (subset, _) = subset_bytes(&provider, "fi", &options).unwrap();
+        assert_eq!(shape(&subset, "fi").len(), 2);
+    }
+
+    #[test]
+    fn minimal_profile_drops_layout_tables() {
+        let data = with_layout(FONT);
+        let font = load(&data).unwrap();
+        let provider = font.table_provider(0).unwrap();
+        let (subset, _) = subset_bytes(&provider, "AVfiZ", &SubsetOptions::default()).unwrap();
+        let sfnt = Sfnt::parse(&subset).unwrap();
+        for t in [tag::GDEF, tag::GSUB, tag::GPOS] {
+            assert!(sfnt.table(t).is_none(), "{} kept", tag_display(t));
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! GSUB 闭包
+//!
+//! 从文本直接映射的字形出发，找出通过 GSUB 替换能够得到的所有字形，例如连字、上下文替换、
+//! 小型大写字母和竖排字形，避免子集化后这些特性失效
+
+use std::collections::{BTreeSet, HashSet};
+
+use crate::font_info::BoxError;
+use crate::layout::coverage;
+use crate::sfnt::{read_u16, read_u32};
+
+const SINGLE: u16 = 1;
+const MULTIPLE: u16 = 2;
+const ALTERNATE: u16 = 3;
+const LIGATURE: u16 = 4;
+const CONTEXT: u16 = 5;
+const CHAINED_CONTEXT: u16 = 6;
+const EXTENSION: u16 = 7;
+const REVERSE_CHAINED: u16 = 8;
+
+/// 把 GSUB 能够从 glyphs 得到的字形加入 glyphs
+///
+/// features 为空时使用所有特性，否则只使用其中列出的特性，GSUB 中不存在的特性会被忽略
+pub(crate) fn closure(
+    gsub: &[u8],
+    glyphs: &mut BTreeSet<u16>,
+    features: &[u32],
+) -> Result<(), BoxError> {
+    let feature_list = usize::from(read_u16(gsub, 6)?);
+    let lookup_list = usize::from(read_u16(gsub, 8)?);
+
+    let mut lookups = BTreeSet::new();
+    for i in 0..usize::from(read_u16(gsub, feature_list)?) {
+        let record = feature_list + 2 + i * 6;
+        if !features.is_empty() && !features.contains(&read_u32(gsub, record)?) {
+            continue;
+        }
+        let feature = feature_list + usize::from(read_u16(gsub, record + 4)?);
+        for l in 0..usize::from(read_u16(gsub, feature + 2)?) {
+            lookups.insert(read_u16(gsub, feature + 4 + l * 2)?);
+        }
+    }
+
+    let lookup_count = read_u16(gsub, lookup_list)?;
+    let mut subtables = Vec::new();
+    // 上下文替换引用的查找同样需要应用，这里不判断上下文是否能够匹配
+    let mut pending = lookups.iter().copied().collect::<Vec<_>>();
+    let mut visited = HashSet::new();
+    while let Some(index) = pending.pop() {
+        if index >= lookup_count || !visited.insert(index) {
+            continue;
+        }
+        let lookup =
+            lookup_list + usize::from(read_u16(gsub, lookup_list + 2 + usize::from(index) * 2)?);
+        let lookup_type = read_u16(gsub, lookup)?;
+        for s in 0..usize::from(read_u16(gsub, lookup + 4)?) {
+            let mut subtable = lookup + usize::from(read_u16(gsub, lookup + 6 + s * 2)?);
+            let mut subtable_type = lookup_type;
+            if subtable_type == EXTENSION {
+                subtable_type = read_u16(gsub, subtable + 2)?;
+                subtable += read_u32(gsub, subtable + 4)? as usize;
+            }
+            match subtable_type {
+                CONTEXT | CHAINED_CONTEXT => {
+                    pending.extend(nested_lookups(gsub, subtable, subtable_type)?)
+                }
+                _ => subtables.push((subtable_type, subtable)),
+            }
+        }
+    }
+
+    // 替换得到的字形可能继续被替换，直到不再产生新字形
+    loop {
+        let count = glyphs.len();
+        for (subtable_type, subtable) in subtables.iter() {
+            apply(gsub, *subtable_type, *subtable, glyphs)?;
+        }
+        if glyphs.len() == count {
+            return Ok(());
+        }
+    }
+}
+
+/// 应用一个替换子表，把能够得到的字形加入 glyphs
+fn apply(
+    gsub: &[u8],
+    subtable_type: u16,
+    subtable: usize,
+    glyphs: &mut BTreeSet<u16>,
+) -> Result<(), BoxError> {
+    let format = read_u16(gsub, subtable)?;
+    let coverage = coverage(gsub, subtable + usize::from(read_u16(gsub, subtable + 2)?))?;
+    let mut found = Vec::new();
+    match (subtable_type, format) {
+        (SINGLE, 1) => {
+            let delta = read_u16(gsub, subtable + 4)?;
+            for glyph in coverage.iter().filter(|g| glyphs.contains(g)) {
+                found.push(glyph.wrapping_add(delta));
+            }
+        }
+        (SINGLE, 2) => {
+            for (i, glyph) in coverage.iter().enumerate() {
+                if glyphs.contains(glyph) {
+                    found.push(read_u16(gsub, subtable + 6 + i * 2)?);
+                }
+            }
+        }
+        // 多重替换和备选替换的结构相同，都是每个覆盖字形对应一组字形
+        (MULTIPLE, 1) | (ALTERNATE, 1) => {
+            for (i, glyph) in coverage.iter().enumerate() {
+                if !glyphs.contains(glyph) {
+                    continue;
+                }
+                let set = subtable + usize::from(read_u16(gsub, subtable + 6 + i * 2)?);
+                for g in 0..usize::from(read_u16(gsub, set)?) {
+                    found.push(read_u16(gsub, set + 2 + g * 2)?);
+                }
+            }
+        }
+        (LIGATURE, 1) => {
+            for (i, glyph) in coverage.iter().enumerate() {
+                if !glyphs.contains(glyph) {
+                    continue;
+                }
+                let set = subtable + usize::from(read_u16(gsub, subtable + 6 + i * 2)?);
+                for l in 0..usize::from(read_u16(gsub, set)?) {
+                    let ligature = set + usize::from(read_u16(gsub, set + 2 + l * 2)?);
+                    let components = usize::from(read_u16(gsub, ligature + 2)?);
+                    // 第一个组件就是覆盖字形，其余组件都存在时才能组成连字
+                    let mut complete = true;
+                    for c in 1..components {
+                        complete &= glyphs.contains(&read_u16(gsub, ligature + 2 + c * 2)?);
+                    }
+                    if complete {
+                        found.push(read_u16(gsub, ligature)?);
+                    }
+                }
+            }
+        }
+        (REVERSE_CHAINED, 1) => {
+            let backtrack = usize::from(read_u16(gsub, subtable + 4)?);
+            let lookahead_at = subtable + 6 + backtrack * 2;
+            let lookahead = usize::from(read_u16(gsub, lookahead_at)?);
+            let substitutes = lookahead_at + 4 + lookahead * 2;
+            for (i, glyph) in coverage.iter().enumerate() {
+                if glyphs.contains(glyph) {
+                    found.push(read_u16(gsub, substitutes + i * 2)?);
+                }
+            }
+        }
+        _ => s_info!("skip GSUB lookup type {subtable_type} format {format}"),
+    }
+    glyphs.extend(found);
+    Ok(())
+}
+
+/// 上下文替换中引用的查找
+fn nested_lookups(gsub: &[u8], subtable: usize, subtable_type: u16) -> Result<Vec<u16>, BoxError> {
+    let format = read_u16(gsub, subtable)?;
+    // 所有 SubstLookupRecord 数组，分别是数量的位置和数组的位置
+    let mut records = Vec::new();
+    match (subtable_type, format) {
+        // 格式1和格式2都是规则集合，只是匹配方式不同
+        (CONTEXT | CHAINED_CONTEXT, 1 | 2) => {
+            let sets_at = match (subtable_type, format) {
+                (_, 1) => subtable + 4,
+                (CONTEXT, _) => subtable + 6,
+                _ => subtable + 10,
+            };
+            for s in 0..usize::from(read_u16(gsub, sets_at)?) {
+                let set_offset = usize::from(read_u16(gsub, sets_at + 2 + s * 2)?);
+                if set_offset == 0 {
+                    continue;
+                }
+                let set = subtable + set_offset;
+                for r in 0..usize::from(read_u16(gsub, set)?) {
+                    let rule = set + usize::from(read_u16(gsub, set + 2 + r * 2)?);
+                    if subtable_type == CONTEXT {
+                        // glyphCount, substitutionCount, inputSequence[glyphCount - 1]
+                        let glyph_count = usize::from(read_u16(gsub, rule)?);
+                        records.push((rule + 2, rule + 4 + glyph_count.saturating_sub(1) * 2));
+                    } else {
+                        let at = chained_records(gsub, rule, 1)?;
+                        records.push((at, at + 2));
+                    }
+                }
+            }
+        }
+        (CONTEXT, 3) => {
+            // glyphCount, substitutionCount, coverageOffsets[glyphCount]
+            let glyph_count = usize::from(read_u16(gsub, subtable + 2)?);
+            records.push((subtable + 4, subtable + 6 + glyph_count * 2));
+        }
+        (CHAINED_CONTEXT, 3) => {
+            let at = chained_records(gsub, subtable + 2, 0)?;
+            records.push((at, at + 2));
+        }
+        _ => s_info!("skip GSUB context type {subtable_type} format {format}"),
+    }
+
+    let mut lookups = Vec::new();
+    for (count_at, records_at) in records {
+        for i in 0..usize::from(read_u16(gsub, count_at)?) {
+            // SubstLookupRecord: sequenceIndex, lookupListIndex
+            lookups.push(read_u16(gsub, records_at + i * 4 + 2)?);
+        }
+    }
+    Ok(lookups)
+}
+
+/// 跳过链式上下文的回溯、输入和前瞻序列，返回 substitutionCount 的位置
+///
+/// 规则中的输入序列不包含第一个字形，格式3的输入覆盖表则包含，由 skip 区分
+fn chained_records(gsub: &[u8], start: usize, skip: usize) -> Result<usize, BoxError> {
+    let backtrack = usize::from(read_u16(gsub, start)?);
+    let input_at = start + 2 + backtrack * 2;
+    let input = usize::from(read_u16(gsub, input_at)?);
+    let lookahead_at = input_at + 2 + input.saturating_sub(skip) * 2;
+    let lookahead = usize::from(read_u16(gsub, lookahead_at)?);
+    Ok(lookahead_at + 2 + lookahead * 2)
+}
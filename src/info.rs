@@ -0,0 +1,186 @@
+//! `fontview cli info`，输出字体的基本信息
+
+use std::path::Path;
+use std::time::Duration;
+
+use fontview::font_info::{FontError, tag_display};
+use fontview::inspect::{self, FaceInfo, FontInfo, LineMetrics};
+use serde_json::{Value, json};
+
+/// 输出字体信息，json 为 true 时输出 JSON
+pub(crate) fn run(path: &Path, json: bool) -> Result<(), FontError> {
+    let data = std::fs::read(path).map_err(|e| FontError::io(path, e))?;
+    let info = inspect::inspect(&data)?;
+    if json {
+        println!("{}", to_json(path, data.len(), &info));
+    } else {
+        print_text(path, data.len(), &info);
+    }
+    Ok(())
+}
+
+/// head 中的时间，从 1904 年开始，按 UTC 显示
+fn date_display(value: i64) -> String {
+    match u64::try_from(value) {
+        Ok(value) => crate::custom_log::do_time_display(value, 1904, Duration::ZERO),
+        Err(_) => value.to_string(),
+    }
+}
+
+/// 嵌入许可的说明，同时设置了多个许可位时以限制最少的为准
+pub(crate) fn fs_type_display(fs_type: u16) -> String {
+    let mut flags = vec![match fs_type & 0xf {
+        0 => "installable",
+        f if f & 0x8 != 0 => "editable",
+        f if f & 0x4 != 0 => "preview & print",
+        f if f & 0x2 != 0 => "restricted",
+        _ => "unknown",
+    }];
+    if fs_type & 0x100 != 0 {
+        flags.push("no subsetting");
+    }
+    if fs_type & 0x200 != 0 {
+        flags.push("bitmap only");
+    }
+    format!("0x{fs_type:04X} ({})", flags.join(", "))
+}
+
+pub(crate) fn metrics_display(m: &LineMetrics) -> String {
+    format!(
+        "ascender {}, descender {}, lineGap {}",
+        m.ascender, m.descender, m.line_gap
+    )
+}
+
+fn print_text(path: &Path, size: usize, info: &FontInfo) {
+    println!("file:        {}", path.display());
+    println!("size:        {size}");
+    println!("container:   {}", info.container);
+    println!("faces:       {}", info.faces.len());
+    for (index, face) in info.faces.iter().enumerate() {
+        println!();
+        println!("face {index}:");
+        println!("  glyphs:      {}", face.glyphs);
+        println!("  unitsPerEm:  {}", face.units_per_em);
+        println!("  revision:    {:.3}", face.revision);
+        if let Some(angle) = face.italic_angle {
+            println!("  italicAngle: {angle}");
+        }
+        println!("  created:     {}", date_display(face.created));
+        println!("  modified:    {}", date_display(face.modified));
+        if let Some(os2) = &face.os2 {
+            println!("  vendor:      {}", os2.vendor);
+            println!("  weight:      {}", os2.weight);
+            println!("  width:       {}", os2.width);
+            println!("  fsType:      {}", fs_type_display(os2.fs_type));
+            if let Some(typo) = &os2.typo {
+                println!("  typo:        {}", metrics_display(typo));
+            }
+            if let Some((ascent, descent)) = os2.win {
+                println!("  win:         ascent {ascent}, descent {descent}");
+            }
+        }
+        if let Some(hhea) = &face.horizontal {
+            println!("  hhea:        {}", metrics_display(hhea));
+        }
+        if let Some(vhea) = &face.vertical {
+            println!("  vhea:        {}", metrics_display(vhea));
+        }
+        println!("  tables:");
+        for (tag, len) in face.tables.iter() {
+            println!("    {:<6}{len:>10}", tag_display(*tag));
+        }
+        println!("  names:");
+        println!(
+            "    {:<4}{:<24}{:<28}{:<10}VALUE",
+            "ID", "NAME", "ENCODING", "LANGUAGE"
+        );
+        for r in face.names.iter() {
+            let encoding = format!("{}/{} {}", r.platform, r.encoding, r.encoding_display());
+            let language = r
+                .language_tag
+                .clone()
+                .unwrap_or_else(|| format!("0x{:04X}", r.language));
+            println!(
+                "    {:<4}{:<24}{:<28}{:<10}{}",
+                r.name_id,
+                r.name_id_display(),
+                encoding,
+                language,
+                r.value.as_deref().unwrap_or("(undecodable)")
+            );
+        }
+    }
+}
+
+fn face_json(face: &FaceInfo) -> Value {
+    let metrics = |m: &LineMetrics| {
+        json!({
+            "ascender": m.ascender,
+            "descender": m.descender,
+            "line_gap": m.line_gap,
+        })
+    };
+    json!({
+        "glyphs": face.glyphs,
+        "units_per_em": face.units_per_em,
+        "revision": face.revision,
+        "italic_angle": face.italic_angle,
+        "created": date_display(face.created),
+        "modified": date_display(face.modified),
+        "os2": face.os2.as_ref().map(|os2| json!({
+            "weight": os2.weight,
+            "width": os2.width,
+            "fs_type": os2.fs_type,
+            "vendor": os2.vendor,
+            "typo": os2.typo.as_ref().map(metrics),
+            "win": os2.win.map(|(ascent, descent)| json!({ "ascent": ascent, "descent": descent })),
+        })),
+        "hhea": face.horizontal.as_ref().map(metrics),
+        "vhea": face.vertical.as_ref().map(metrics),
+        "tables": face
+            .tables
+            .iter()
+            .map(|(tag, len)| (tag_display(*tag), json!(len)))
+            .collect::<serde_json::Map<_, _>>(),
+        "names": face
+            .names
+            .iter()
+            .map(|r| json!({
+                "name_id": r.name_id,
+                "platform": r.platform,
+                "encoding": r.encoding,
+                "language": r.language,
+                "language_tag": r.language_tag,
+                "value": r.value,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn to_json(path: &Path, size: usize, info: &FontInfo) -> String {
+    let value = json!({
+        "file": path.display().to_string(),
+        "size": size,
+        "container": info.container.to_string(),
+        "faces": info.faces.iter().map(face_json).collect::<Vec<_>>(),
+    });
+    serde_json::to_string_pretty(&value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fs_type_least_restrictive() {
+        assert_eq!(fs_type_display(0), "0x0000 (installable)");
+        assert_eq!(fs_type_display(0x2), "0x0002 (restricted)");
+        assert_eq!(fs_type_display(0x6), "0x0006 (preview & print)");
+        assert_eq!(fs_type_display(0xE), "0x000E (editable)");
+        assert_eq!(
+            fs_type_display(0x304),
+            "0x0304 (preview & print, no subsetting, bitmap only)"
+        );
+    }
+}
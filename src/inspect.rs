@@ -0,0 +1,332 @@
+//! 读取字体的基本信息，用于 `fontview cli info`
+
+use allsorts::tables::FontTableProvider;
+use allsorts::tag;
+
+use crate::font_info::{self, BoxError, FontError};
+use crate::sfnt::{read_u16, read_u32};
+
+/// 字体文件的容器格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    /// CFF 轮廓的 OpenType
+    Otf,
+    /// TrueType 轮廓
+    Ttf,
+    /// 字体集合，包括 otc
+    Ttc,
+    Woff,
+    Woff2,
+}
+
+impl Container {
+    /// 根据文件开头的标识判断格式
+    pub fn detect(data: &[u8]) -> Container {
+        match data.get(0..4) {
+            Some(b"OTTO") => Container::Otf,
+            Some(b"ttcf") => Container::Ttc,
+            Some(b"wOFF") => Container::Woff,
+            Some(b"wOF2") => Container::Woff2,
+            _ => Container::Ttf,
+        }
+    }
+}
+
+impl std::fmt::Display for Container {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Container::Otf => "OTF",
+            Container::Ttf => "TTF",
+            Container::Ttc => "TTC",
+            Container::Woff => "WOFF",
+            Container::Woff2 => "WOFF2",
+        })
+    }
+}
+
+/// 字体文件信息
+#[derive(Debug, Clone)]
+pub struct FontInfo {
+    pub container: Container,
+    /// 每个字体的信息，普通字体只有一个
+    pub faces: Vec<FaceInfo>,
+}
+
+/// 单个字体的信息
+#[derive(Debug, Clone, Default)]
+pub struct FaceInfo {
+    /// 表名和解压后的大小，按表名排序
+    pub tables: Vec<(u32, usize)>,
+    /// maxp 中的字形数量
+    pub glyphs: u16,
+    pub units_per_em: u16,
+    /// head 中的字体版本
+    pub revision: f64,
+    /// post 中的斜体角度，没有 post 表时为 None
+    pub italic_angle: Option<f64>,
+    /// head 中的创建时间，从 1904-01-01 开始的秒数
+    pub created: i64,
+    /// head 中的修改时间，从 1904-01-01 开始的秒数
+    pub modified: i64,
+    /// 没有 OS/2 表时为 None
+    pub os2: Option<Os2>,
+    /// hhea 中的横排度量
+    pub horizontal: Option<LineMetrics>,
+    /// vhea 中的竖排度量
+    pub vertical: Option<LineMetrics>,
+    pub names: Vec<NameRecord>,
+}
+
+/// OS/2 表中的字重、字宽、嵌入许可和度量
+#[derive(Debug, Clone, Default)]
+pub struct Os2 {
+    pub weight: u16,
+    pub width: u16,
+    /// 嵌入许可
+    pub fs_type: u16,
+    /// 厂商标识 achVendID
+    pub vendor: String,
+    /// sTypoAscender、sTypoDescender、sTypoLineGap，版本0的短表没有
+    pub typo: Option<LineMetrics>,
+    /// usWinAscent、usWinDescent，版本0的短表没有
+    pub win: Option<(u16, u16)>,
+}
+
+/// 上升、下降和行间距
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LineMetrics {
+    pub ascender: i16,
+    pub descender: i16,
+    pub line_gap: i16,
+}
+
+/// name 表中的一条记录
+#[derive(Debug, Clone)]
+pub struct NameRecord {
+    pub platform: u16,
+    pub encoding: u16,
+    pub language: u16,
+    /// 可读的语言标签，例如 `zh-CN`，未收录的语言为 None
+    pub language_tag: Option<String>,
+    pub name_id: u16,
+    /// 无法解码的编码为 None
+    pub value: Option<String>,
+}
+
+impl NameRecord {
+    /// 名称编号的含义
+    pub fn name_id_display(&self) -> &'static str {
+        match self.name_id {
+            0 => "Copyright",
+            1 => "Family",
+            2 => "Subfamily",
+            3 => "Unique ID",
+            4 => "Full Name",
+            5 => "Version",
+            6 => "PostScript Name",
+            7 => "Trademark",
+            8 => "Manufacturer",
+            9 => "Designer",
+            10 => "Description",
+            11 => "Vendor URL",
+            12 => "Designer URL",
+            13 => "License",
+            14 => "License URL",
+            16 => "Typographic Family",
+            17 => "Typographic Subfamily",
+            18 => "Compatible Full",
+            19 => "Sample Text",
+            20 => "PostScript CID",
+            21 => "WWS Family",
+            22 => "WWS Subfamily",
+            23 => "Light Background Palette",
+            24 => "Dark Background Palette",
+            25 => "Variations PostScript Prefix",
+            _ => "",
+        }
+    }
+
+    /// 平台和编码的名称
+    pub fn encoding_display(&self) -> &'static str {
+        match (self.platform, self.encoding) {
+            (0, _) => "Unicode",
+            (1, 0) => "Mac Roman",
+            (1, 1) => "Mac Japanese",
+            (1, 2) => "Mac Chinese (Traditional)",
+            (1, 3) => "Mac Korean",
+            (1, 7) => "Mac Cyrillic",
+            (1, 25) => "Mac Chinese (Simplified)",
+            (1, _) => "Mac",
+            (3, 0) => "Windows Symbol",
+            (3, 1) => "Windows Unicode BMP",
+            (3, 2) => "Windows ShiftJIS",
+            (3, 3) => "Windows PRC",
+            (3, 4) => "Windows Big5",
+            (3, 5) => "Windows Wansung",
+            (3, 6) => "Windows Johab",
+            (3, 10) => "Windows Unicode Full",
+            (3, _) => "Windows",
+            _ => "",
+        }
+    }
+}
+
+/// 界面上显示名称时偏好的语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayLanguage {
+    #[default]
+    ZhHans,
+    ZhHant,
+    Ja,
+    En,
+}
+
+impl DisplayLanguage {
+    pub const ALL: [DisplayLanguage; 4] = [
+        DisplayLanguage::ZhHans,
+        DisplayLanguage::ZhHant,
+        DisplayLanguage::Ja,
+        DisplayLanguage::En,
+    ];
+
+    /// 语言标签是否属于该语言，Windows 的地区和 Mac 的简繁中文都会转换为对应的标签
+    fn matches(self, tag: &str) -> bool {
+        let tag = tag.to_ascii_lowercase();
+        let prefix = |p: &str| tag == p || tag.starts_with(&format!("{p}-"));
+        match self {
+            DisplayLanguage::ZhHans => prefix("zh-hans") || tag == "zh-cn" || tag == "zh-sg",
+            DisplayLanguage::ZhHant => {
+                prefix("zh-hant") || tag == "zh-tw" || tag == "zh-hk" || tag == "zh-mo"
+            }
+            DisplayLanguage::Ja => prefix("ja"),
+            DisplayLanguage::En => prefix("en"),
+        }
+    }
+}
+
+impl std::fmt::Display for DisplayLanguage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DisplayLanguage::ZhHans => "简体中文",
+            DisplayLanguage::ZhHant => "繁體中文",
+            DisplayLanguage::Ja => "日本語",
+            DisplayLanguage::En => "English",
+        })
+    }
+}
+
+/// 按语言偏好选择名称
+///
+/// 依次使用偏好的语言、英文和其它语言的记录，同一语言按 name_ids 的顺序，最后优先 Windows 平台
+pub fn localized_name<'a>(
+    records: &'a [NameRecord],
+    name_ids: &[u16],
+    language: DisplayLanguage,
+) -> Option<&'a str> {
+    records
+        .iter()
+        .filter(|r| r.value.as_deref().is_some_and(|v| !v.is_empty()))
+        .filter_map(|r| {
+            let id = name_ids.iter().position(|id| *id == r.name_id)?;
+            let tag = r.language_tag.as_deref().unwrap_or_default();
+            let order = if language.matches(tag) {
+                0
+            } else if DisplayLanguage::En.matches(tag) {
+                1
+            } else {
+                2
+            };
+            Some(((order, id, r.platform != 3), r))
+        })
+        .min_by_key(|(rank, _)| *rank)
+        .and_then(|(_, r)| r.value.as_deref())
+}
+
+/// 读取字体 name 表中的所有记录，没有 name 表时为空
+pub fn name_records<F: FontTableProvider>(font_provider: &F) -> Result<Vec<NameRecord>, FontError> {
+    match font_provider.table_data(tag::NAME)? {
+        Some(name) => Ok(crate::rename::records(&name)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// 读取字体文件中所有字体的信息
+pub fn inspect(data: &[u8]) -> Result<FontInfo, FontError> {
+    let container = Container::detect(data);
+    let font = font_info::load(data)?;
+    let faces = (0..font_info::face_count(data))
+        .map(|index| Ok(face_info(&font.table_provider(index)?)?))
+        .collect::<Result<Vec<_>, FontError>>()?;
+    Ok(FontInfo { container, faces })
+}
+
+fn face_info<F: FontTableProvider>(font_provider: &F) -> Result<FaceInfo, BoxError> {
+    let mut tables = Vec::new();
+    for t in font_provider.table_tags().unwrap_or_default() {
+        tables.push((t, font_provider.read_table_data(t)?.len()));
+    }
+    tables.sort();
+
+    let head = font_provider.read_table_data(tag::HEAD)?;
+    let maxp = font_provider.read_table_data(tag::MAXP)?;
+    let metrics = |t: u32| -> Result<Option<LineMetrics>, BoxError> {
+        match font_provider.table_data(t)? {
+            Some(data) => Ok(Some(line_metrics(&data, 4)?)),
+            None => Ok(None),
+        }
+    };
+    let os2 = match font_provider.table_data(tag::OS_2)? {
+        Some(os2) => Some(Os2 {
+            weight: read_u16(&os2, 4)?,
+            width: read_u16(&os2, 6)?,
+            fs_type: read_u16(&os2, 8)?,
+            vendor: os2
+                .get(58..62)
+                .map(|v| String::from_utf8_lossy(v).trim_end().to_string())
+                .unwrap_or_default(),
+            typo: line_metrics(&os2, 68).ok(),
+            win: read_u16(&os2, 74)
+                .and_then(|ascent| Ok((ascent, read_u16(&os2, 76)?)))
+                .ok(),
+        }),
+        None => None,
+    };
+
+    Ok(FaceInfo {
+        tables,
+        glyphs: read_u16(&maxp, 4)?,
+        units_per_em: read_u16(&head, 18)?,
+        revision: read_fixed(&head, 4)?,
+        italic_angle: match font_provider.table_data(crate::sfnt::tag(b"post"))? {
+            Some(post) => Some(read_fixed(&post, 4)?),
+            None => None,
+        },
+        created: read_i64(&head, 20)?,
+        modified: read_i64(&head, 28)?,
+        os2,
+        horizontal: metrics(tag::HHEA)?,
+        vertical: metrics(crate::sfnt::tag(b"vhea"))?,
+        names: name_records(font_provider)?,
+    })
+}
+
+/// 连续的三个 FWORD：上升、下降、行间距
+fn line_metrics(data: &[u8], offset: usize) -> Result<LineMetrics, BoxError> {
+    Ok(LineMetrics {
+        ascender: read_u16(data, offset)? as i16,
+        descender: read_u16(data, offset + 2)? as i16,
+        line_gap: read_u16(data, offset + 4)? as i16,
+    })
+}
+
+/// 16.16 定点数
+fn read_fixed(data: &[u8], offset: usize) -> Result<f64, BoxError> {
+    Ok(f64::from(read_u32(data, offset)? as i32) / 65536.0)
+}
+
+/// head 中的 LONGDATETIME
+fn read_i64(data: &[u8], offset: usize) -> Result<i64, BoxError> {
+    let high = u64::from(read_u32(data, offset)?);
+    let low = u64::from(read_u32(data, offset + 4)?);
+    Ok(((high << 32) | low) as i64)
+}
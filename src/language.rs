@@ -0,0 +1,84 @@
+//! name 表中语言编号对应的 BCP 47 语言标签
+
+/// Windows 平台的 LCID，只包含常用语言
+const WINDOWS: &[(u16, &str)] = &[
+    (0x0401, "ar-SA"),
+    (0x0402, "bg-BG"),
+    (0x0403, "ca-ES"),
+    (0x0404, "zh-TW"),
+    (0x0405, "cs-CZ"),
+    (0x0406, "da-DK"),
+    (0x0407, "de-DE"),
+    (0x0408, "el-GR"),
+    (0x0409, "en-US"),
+    (0x040B, "fi-FI"),
+    (0x040C, "fr-FR"),
+    (0x040D, "he-IL"),
+    (0x040E, "hu-HU"),
+    (0x040F, "is-IS"),
+    (0x0410, "it-IT"),
+    (0x0411, "ja-JP"),
+    (0x0412, "ko-KR"),
+    (0x0413, "nl-NL"),
+    (0x0414, "nb-NO"),
+    (0x0415, "pl-PL"),
+    (0x0416, "pt-BR"),
+    (0x0418, "ro-RO"),
+    (0x0419, "ru-RU"),
+    (0x041A, "hr-HR"),
+    (0x041B, "sk-SK"),
+    (0x041D, "sv-SE"),
+    (0x041E, "th-TH"),
+    (0x041F, "tr-TR"),
+    (0x0421, "id-ID"),
+    (0x0422, "uk-UA"),
+    (0x0424, "sl-SI"),
+    (0x0425, "et-EE"),
+    (0x0426, "lv-LV"),
+    (0x0427, "lt-LT"),
+    (0x0429, "fa-IR"),
+    (0x042A, "vi-VN"),
+    (0x042D, "eu-ES"),
+    (0x0439, "hi-IN"),
+    (0x043E, "ms-MY"),
+    (0x0804, "zh-CN"),
+    (0x0807, "de-CH"),
+    (0x0809, "en-GB"),
+    (0x080A, "es-MX"),
+    (0x080C, "fr-BE"),
+    (0x0816, "pt-PT"),
+    (0x0C04, "zh-HK"),
+    (0x0C07, "de-AT"),
+    (0x0C09, "en-AU"),
+    (0x0C0A, "es-ES"),
+    (0x0C0C, "fr-CA"),
+    (0x1004, "zh-SG"),
+    (0x1009, "en-CA"),
+    (0x1404, "zh-MO"),
+];
+
+/// Mac 平台的语言，下标即语言编号
+const MAC: &[&str] = &[
+    "en", "fr", "de", "it", "nl", "sv", "es", "da", "pt", "no", "he", "ja", "ar", "fi", "el", "is",
+    "mt", "tr", "hr", "zh-Hant", "ur", "hi", "th", "ko", "lt", "pl", "hu", "et", "lv", "se", "fo",
+    "fa", "ru", "zh-Hans", "nl-BE", "ga", "sq", "ro", "cs", "sk", "sl", "yi", "sr", "mk", "bg",
+    "uk", "be", "uz", "kk", "az-Cyrl", "az-Arab", "hy", "ka", "ro-MD", "ky", "tg", "tk", "mn-Mong",
+    "mn-Cyrl", "ps", "ku", "ks", "sd", "bo", "ne", "sa", "mr", "bn", "as", "gu", "pa", "or", "ml",
+    "kn", "ta", "te", "si", "my", "km", "lo", "vi", "id", "tl", "ms", "ms-Arab", "am", "ti", "om",
+    "so", "sw", "rw", "rn", "ny", "mg", "eo",
+];
+
+/// 记录的语言标签，lang_tags 是版本1 name 表中的语言标签，未收录的语言返回 None
+pub(crate) fn tag(platform: u16, language: u16, lang_tags: &[String]) -> Option<String> {
+    if language >= 0x8000 {
+        return lang_tags.get(usize::from(language - 0x8000)).cloned();
+    }
+    match platform {
+        1 => MAC.get(usize::from(language)).map(|t| t.to_string()),
+        3 => WINDOWS
+            .binary_search_by_key(&language, |(id, _)| *id)
+            .ok()
+            .map(|i| WINDOWS[i].1.to_string()),
+        _ => None,
+    }
+}
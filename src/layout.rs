@@ -0,0 +1,1567 @@
+//! 排版表（GSUB、GPOS、GDEF、kern）的子集化
+//!
+//! 只保留子集中字形参与的规则，字形编号替换为子集中的编号。子表重新生成后按内容去重并
+//! 排列，16 位偏移放不下时把所有查找改为扩展查找再排列一次
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::font_info::BoxError;
+use crate::sfnt::{read_u16, read_u32};
+
+/// GSUB 扩展查找类型
+const GSUB_EXTENSION: u16 = 7;
+/// GPOS 扩展查找类型
+const GPOS_EXTENSION: u16 = 9;
+/// LookupFlag 中表示带有 markFilteringSet 的位
+const USE_MARK_FILTERING_SET: u16 = 0x0010;
+/// kern 格式 0 子表单个子表能容纳的字偶数量，再多 length 字段会溢出
+const KERN_PAIRS_PER_SUBTABLE: usize = 10920;
+
+/// 原字形编号到子集中字形编号的映射
+pub(crate) struct GlyphMap(Vec<Option<u16>>);
+
+impl GlyphMap {
+    /// glyph_ids 的下标即子集中的字形编号
+    pub(crate) fn new(glyph_ids: &[u16]) -> Self {
+        let len = glyph_ids.iter().max().map_or(0, |g| usize::from(*g) + 1);
+        let mut map = vec![None; len];
+        for (new, old) in glyph_ids.iter().enumerate() {
+            map[usize::from(*old)] = Some(new as u16);
+        }
+        GlyphMap(map)
+    }
+
+    fn get(&self, old: u16) -> Option<u16> {
+        self.0.get(usize::from(old)).copied().flatten()
+    }
+}
+
+/// 子集化 GSUB
+pub(crate) fn subset_gsub(gsub: &[u8], glyphs: &GlyphMap) -> Result<Vec<u8>, BoxError> {
+    subset_layout(gsub, glyphs, false)
+}
+
+/// 子集化 GPOS
+pub(crate) fn subset_gpos(gpos: &[u8], glyphs: &GlyphMap) -> Result<Vec<u8>, BoxError> {
+    subset_layout(gpos, glyphs, true)
+}
+
+/// 子集化 GDEF，变体数据（ItemVariationStore）不会保留
+pub(crate) fn subset_gdef(gdef: &[u8], glyphs: &GlyphMap) -> Result<Vec<u8>, BoxError> {
+    let layout = Layout::new(gdef, glyphs, false);
+    let version = read_u32(gdef, 0)?;
+    let mark_glyph_sets = if version >= 0x0001_0002 {
+        layout.offset(0, 12)?
+    } else {
+        None
+    };
+
+    let mut out = Object::default();
+    out.u32(match mark_glyph_sets {
+        Some(_) => 0x0001_0002,
+        None => 0x0001_0000,
+    });
+    out.offset(
+        layout
+            .offset(0, 4)?
+            .map(|at| layout.class_def(at))
+            .transpose()?,
+    );
+    out.offset(
+        layout
+            .offset(0, 6)?
+            .map(|at| layout.attach_list(at))
+            .transpose()?
+            .flatten(),
+    );
+    out.offset(
+        layout
+            .offset(0, 8)?
+            .map(|at| layout.lig_caret_list(at))
+            .transpose()?
+            .flatten(),
+    );
+    out.offset(
+        layout
+            .offset(0, 10)?
+            .map(|at| layout.class_def(at))
+            .transpose()?,
+    );
+    if let Some(at) = mark_glyph_sets {
+        out.offset(Some(layout.mark_glyph_sets(at)?));
+    }
+    pack(out).ok_or_else(|| "GDEF too large".into())
+}
+
+/// 子集化 kern，只保留格式 0 子表中两个字形都在子集中的字偶
+///
+/// 没有字偶剩下或者是 Apple 的 kern 格式时返回 None
+pub(crate) fn subset_kern(kern: &[u8], glyphs: &GlyphMap) -> Result<Option<Vec<u8>>, BoxError> {
+    if read_u16(kern, 0)? != 0 {
+        s_info!("Skip kern table in Apple format");
+        return Ok(None);
+    }
+    let mut subtables = Vec::new();
+    let mut at = 4;
+    for _ in 0..read_u16(kern, 2)? {
+        let length = usize::from(read_u16(kern, at + 2)?);
+        let coverage = read_u16(kern, at + 4)?;
+        // 高 8 位是子表格式
+        if coverage >> 8 == 0 {
+            let mut pairs = Vec::new();
+            for i in 0..usize::from(read_u16(kern, at + 6)?) {
+                let record = at + 14 + i * 6;
+                if let (Some(left), Some(right)) = (
+                    glyphs.get(read_u16(kern, record)?),
+                    glyphs.get(read_u16(kern, record + 2)?),
+                ) {
+                    pairs.push((left, right, read_u16(kern, record + 4)?));
+                }
+            }
+            pairs.sort_unstable();
+            pairs.dedup_by_key(|p| (p.0, p.1));
+            for chunk in pairs.chunks(KERN_PAIRS_PER_SUBTABLE) {
+                subtables.push(kern_subtable(coverage, chunk));
+            }
+        }
+        // 格式 0 的 length 可能溢出，按字偶数量跳过
+        at += match coverage >> 8 {
+            0 => 14 + usize::from(read_u16(kern, at + 6)?) * 6,
+            _ => length,
+        };
+    }
+    if subtables.is_empty() {
+        return Ok(None);
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&0u16.to_be_bytes());
+    out.extend_from_slice(&(subtables.len() as u16).to_be_bytes());
+    for subtable in subtables {
+        out.extend_from_slice(&subtable);
+    }
+    Ok(Some(out))
+}
+
+/// 生成 kern 格式 0 子表，pairs 已按字形排序
+fn kern_subtable(coverage: u16, pairs: &[(u16, u16, u16)]) -> Vec<u8> {
+    let count = pairs.len() as u16;
+    // searchRange 等字段按二分查找的要求计算
+    let entry_selector = count.max(1).ilog2() as u16;
+    let search_range = (1u16 << entry_selector) * 6;
+    let mut out = Vec::with_capacity(14 + pairs.len() * 6);
+    for v in [
+        0,
+        (14 + pairs.len() * 6) as u16,
+        coverage,
+        count,
+        search_range,
+        entry_selector,
+        count * 6 - search_range,
+    ] {
+        out.extend_from_slice(&v.to_be_bytes());
+    }
+    for (left, right, value) in pairs {
+        for v in [left, right, value] {
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+    out
+}
+
+/// 读取 Coverage 表，按覆盖下标的顺序返回字形
+pub(crate) fn coverage(data: &[u8], at: usize) -> Result<Vec<u16>, BoxError> {
+    let count = usize::from(read_u16(data, at + 2)?);
+    match read_u16(data, at)? {
+        1 => (0..count).map(|i| read_u16(data, at + 4 + i * 2)).collect(),
+        2 => {
+            let mut glyphs = Vec::new();
+            for i in 0..count {
+                let range = at + 4 + i * 6;
+                let (start, end) = (read_u16(data, range)?, read_u16(data, range + 2)?);
+                glyphs.extend(start..=end);
+            }
+            Ok(glyphs)
+        }
+        format => Err(format!("unknown coverage format {format}").into()),
+    }
+}
+
+/// 生成 Coverage 表，glyphs 已排序并去重
+fn coverage_table(glyphs: &[u16]) -> Object {
+    let ranges = ranges(glyphs.iter().map(|g| (*g, 0)));
+    let mut out = Object::default();
+    // 连续的字形较多时格式 2 更小
+    if ranges.len() * 3 < glyphs.len() {
+        out.u16(2);
+        out.u16(ranges.len() as u16);
+        let mut index = 0;
+        for (start, end, _) in ranges {
+            out.u16(start);
+            out.u16(end);
+            out.u16(index);
+            index += end - start + 1;
+        }
+    } else {
+        out.u16(1);
+        out.u16(glyphs.len() as u16);
+        for glyph in glyphs {
+            out.u16(*glyph);
+        }
+    }
+    out
+}
+
+/// 生成 ClassDef 表，classes 按字形排序，不包含类别 0
+fn class_def_table(classes: &[(u16, u16)]) -> Object {
+    let ranges = ranges(classes.iter().copied());
+    let mut out = Object::default();
+    let dense = classes
+        .first()
+        .zip(classes.last())
+        .map_or(0, |(first, last)| usize::from(last.0 - first.0) + 1);
+    if !classes.is_empty() && dense <= ranges.len() * 3 {
+        out.u16(1);
+        out.u16(classes[0].0);
+        out.u16(dense as u16);
+        let mut next = classes.iter().peekable();
+        for glyph in classes[0].0..=classes[classes.len() - 1].0 {
+            out.u16(next.next_if(|c| c.0 == glyph).map_or(0, |c| c.1));
+        }
+    } else {
+        out.u16(2);
+        out.u16(ranges.len() as u16);
+        for (start, end, class) in ranges {
+            out.u16(start);
+            out.u16(end);
+            out.u16(class);
+        }
+    }
+    out
+}
+
+/// 合并连续且值相同的字形为 (起始, 结束, 值)
+fn ranges(glyphs: impl Iterator<Item = (u16, u16)>) -> Vec<(u16, u16, u16)> {
+    let mut ranges: Vec<(u16, u16, u16)> = Vec::new();
+    for (glyph, value) in glyphs {
+        match ranges.last_mut() {
+            Some(last) if last.1.checked_add(1) == Some(glyph) && last.2 == value => last.1 = glyph,
+            _ => ranges.push((glyph, glyph, value)),
+        }
+    }
+    ranges
+}
+
+/// 重新生成的 GSUB、GPOS 查找
+struct Lookup {
+    /// 查找类型，扩展查找时是实际的类型
+    kind: u16,
+    flag: u16,
+    mark_filtering_set: Option<u16>,
+    /// 原字体中是扩展查找
+    extension: bool,
+    subtables: Vec<Object>,
+}
+
+/// 读取 GSUB、GPOS 或 GDEF 的共用状态
+struct Layout<'a> {
+    data: &'a [u8],
+    glyphs: &'a GlyphMap,
+    gpos: bool,
+    /// 原查找编号到新编号，第一遍子集化时为 None，查找编号不变
+    lookups: Option<Vec<Option<u16>>>,
+}
+
+fn subset_layout(data: &[u8], glyphs: &GlyphMap, gpos: bool) -> Result<Vec<u8>, BoxError> {
+    let mut layout = Layout::new(data, glyphs, gpos);
+    let lookup_list = layout.offset(0, 8)?;
+    let offsets = match lookup_list {
+        Some(list) => (0..layout.count(list)?)
+            .map(|i| Ok(list + usize::from(layout.u16(list + 2 + i * 2)?)))
+            .collect::<Result<Vec<_>, BoxError>>()?,
+        None => Vec::new(),
+    };
+
+    // 第一遍确定哪些查找还有子表，删除空查找后第二遍重新生成引用其它查找的上下文规则
+    let mut lookups = offsets
+        .iter()
+        .map(|at| layout.lookup(*at))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut next = 0;
+    layout.lookups = Some(
+        lookups
+            .iter()
+            .map(|l| {
+                l.as_ref().map(|_| {
+                    next += 1;
+                    next - 1
+                })
+            })
+            .collect(),
+    );
+    for (lookup, at) in lookups.iter_mut().zip(&offsets) {
+        if lookup
+            .as_ref()
+            .is_some_and(|l| layout.is_contextual(l.kind))
+        {
+            *lookup = layout.lookup(*at)?;
+        }
+    }
+    let lookups = lookups.into_iter().flatten().collect::<Vec<_>>();
+
+    for promote in [false, true] {
+        let mut out = Object::default();
+        // 不保留 FeatureVariations，版本固定为 1.0
+        out.u32(0x0001_0000);
+        out.offset(
+            layout
+                .offset(0, 4)?
+                .map(|at| layout.script_list(at))
+                .transpose()?,
+        );
+        out.offset(
+            layout
+                .offset(0, 6)?
+                .map(|at| layout.feature_list(at))
+                .transpose()?,
+        );
+        let mut list = Object::default();
+        list.u16(lookups.len() as u16);
+        for lookup in &lookups {
+            list.offset(Some(layout.write_lookup(lookup, promote)));
+        }
+        out.offset(Some(list));
+        if let Some(packed) = pack(out) {
+            return Ok(packed);
+        }
+    }
+    Err(format!("{} too large", if gpos { "GPOS" } else { "GSUB" }).into())
+}
+
+impl<'a> Layout<'a> {
+    fn new(data: &'a [u8], glyphs: &'a GlyphMap, gpos: bool) -> Self {
+        Layout {
+            data,
+            glyphs,
+            gpos,
+            lookups: None,
+        }
+    }
+
+    fn u16(&self, at: usize) -> Result<u16, BoxError> {
+        read_u16(self.data, at)
+    }
+
+    fn count(&self, at: usize) -> Result<usize, BoxError> {
+        Ok(usize::from(self.u16(at)?))
+    }
+
+    /// base 处的表中第 field 字节的 16 位偏移，为 0 时返回 None
+    fn offset(&self, base: usize, field: usize) -> Result<Option<usize>, BoxError> {
+        let offset = self.u16(base + field)?;
+        Ok((offset != 0).then(|| base + usize::from(offset)))
+    }
+
+    /// 不能为空的 16 位偏移
+    fn child(&self, base: usize, field: usize) -> Result<usize, BoxError> {
+        Ok(base + usize::from(self.u16(base + field)?))
+    }
+
+    /// 复制一段原始数据
+    fn bytes(&self, at: usize, len: usize) -> Result<Object, BoxError> {
+        let data = self
+            .data
+            .get(at..at + len)
+            .ok_or("unexpected end of font data")?;
+        Ok(Object {
+            data: data.to_vec(),
+            links: Vec::new(),
+        })
+    }
+
+    /// 读取字形编号并转换为子集中的编号
+    fn glyph(&self, at: usize) -> Result<Option<u16>, BoxError> {
+        Ok(self.glyphs.get(self.u16(at)?))
+    }
+
+    /// 覆盖的字形中在子集里的部分，返回 (新字形编号, 覆盖下标)，按新编号排序
+    fn covered(&self, at: usize) -> Result<Vec<(u16, usize)>, BoxError> {
+        let mut covered = coverage(self.data, at)?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, g)| Some((self.glyphs.get(g)?, i)))
+            .collect::<Vec<_>>();
+        covered.sort_unstable();
+        covered.dedup_by_key(|c| c.0);
+        Ok(covered)
+    }
+
+    /// 子集化 Coverage 表，没有字形剩下时返回 None
+    fn coverage(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        let glyphs = self.covered(at)?.iter().map(|c| c.0).collect::<Vec<_>>();
+        Ok((!glyphs.is_empty()).then(|| coverage_table(&glyphs)))
+    }
+
+    /// 读取 ClassDef 表中子集字形的类别，按新字形编号排序，不包含类别 0
+    fn classes(&self, at: usize) -> Result<Vec<(u16, u16)>, BoxError> {
+        let mut classes = Vec::new();
+        match self.u16(at)? {
+            1 => {
+                let start = self.u16(at + 2)?;
+                for i in 0..self.count(at + 4)? {
+                    let class = self.u16(at + 6 + i * 2)?;
+                    if let Some(glyph) = self.glyphs.get(start.wrapping_add(i as u16)) {
+                        classes.push((glyph, class));
+                    }
+                }
+            }
+            2 => {
+                for i in 0..self.count(at + 2)? {
+                    let range = at + 4 + i * 6;
+                    let class = self.u16(range + 4)?;
+                    for old in self.u16(range)?..=self.u16(range + 2)? {
+                        if let Some(glyph) = self.glyphs.get(old) {
+                            classes.push((glyph, class));
+                        }
+                    }
+                }
+            }
+            format => return Err(format!("unknown class definition format {format}").into()),
+        }
+        classes.retain(|c| c.1 != 0);
+        classes.sort_unstable();
+        classes.dedup_by_key(|c| c.0);
+        Ok(classes)
+    }
+
+    /// 子集化 ClassDef 表
+    fn class_def(&self, at: usize) -> Result<Object, BoxError> {
+        Ok(class_def_table(&self.classes(at)?))
+    }
+
+    fn is_contextual(&self, kind: u16) -> bool {
+        match self.gpos {
+            true => matches!(kind, 7 | 8),
+            false => matches!(kind, 5 | 6),
+        }
+    }
+
+    /// 子集化查找，没有子表剩下时返回 None
+    fn lookup(&self, at: usize) -> Result<Option<Lookup>, BoxError> {
+        let extension_kind = if self.gpos {
+            GPOS_EXTENSION
+        } else {
+            GSUB_EXTENSION
+        };
+        let mut kind = self.u16(at)?;
+        let flag = self.u16(at + 2)?;
+        let count = self.count(at + 4)?;
+        let extension = kind == extension_kind;
+        let mut subtables = Vec::new();
+        for i in 0..count {
+            let mut subtable = self.child(at, 6 + i * 2)?;
+            if extension {
+                kind = self.u16(subtable + 2)?;
+                subtable += read_u32(self.data, subtable + 4)? as usize;
+            }
+            // 无法识别的子表只影响该子表本身
+            match self.subtable(kind, subtable) {
+                Ok(Some(subtable)) => subtables.push(subtable),
+                Ok(None) => {}
+                Err(e) => s_info!("Skip lookup subtable of type {kind}: {e}"),
+            }
+        }
+        if subtables.is_empty() {
+            return Ok(None);
+        }
+        let mark_filtering_set = match flag & USE_MARK_FILTERING_SET {
+            0 => None,
+            _ => Some(self.u16(at + 6 + count * 2)?),
+        };
+        Ok(Some(Lookup {
+            kind,
+            flag,
+            mark_filtering_set,
+            extension,
+            subtables,
+        }))
+    }
+
+    /// 生成查找，promote 为 true 时所有子表都通过扩展查找引用
+    fn write_lookup(&self, lookup: &Lookup, promote: bool) -> Object {
+        let extension_kind = if self.gpos {
+            GPOS_EXTENSION
+        } else {
+            GSUB_EXTENSION
+        };
+        let extension = promote || lookup.extension;
+        let mut out = Object::default();
+        out.u16(if extension {
+            extension_kind
+        } else {
+            lookup.kind
+        });
+        out.u16(lookup.flag);
+        out.u16(lookup.subtables.len() as u16);
+        for subtable in &lookup.subtables {
+            if extension {
+                let mut stub = Object::default();
+                stub.u16(1);
+                stub.u16(lookup.kind);
+                stub.offset32(Some(subtable.clone()));
+                out.offset(Some(stub));
+            } else {
+                out.offset(Some(subtable.clone()));
+            }
+        }
+        if let Some(set) = lookup.mark_filtering_set {
+            out.u16(set);
+        }
+        out
+    }
+
+    /// 原查找编号对应的新编号，查找已被删除时返回 None
+    fn lookup_index(&self, old: u16) -> Option<u16> {
+        match &self.lookups {
+            Some(lookups) => lookups.get(usize::from(old)).copied().flatten(),
+            None => Some(old),
+        }
+    }
+
+    /// 复制 ScriptList，语言系统中指向的特性编号不变
+    fn script_list(&self, at: usize) -> Result<Object, BoxError> {
+        let mut out = Object::default();
+        let count = self.count(at)?;
+        out.u16(count as u16);
+        for i in 0..count {
+            out.bytes(&self.bytes(at + 2 + i * 6, 4)?.data);
+            let script = self.child(at, 2 + i * 6 + 4)?;
+            let mut script_out = Object::default();
+            script_out.offset(
+                self.offset(script, 0)?
+                    .map(|at| self.lang_sys(at))
+                    .transpose()?,
+            );
+            let lang_count = self.count(script + 2)?;
+            script_out.u16(lang_count as u16);
+            for l in 0..lang_count {
+                script_out.bytes(&self.bytes(script + 4 + l * 6, 4)?.data);
+                script_out.offset(Some(self.lang_sys(self.child(script, 4 + l * 6 + 4)?)?));
+            }
+            out.offset(Some(script_out));
+        }
+        Ok(out)
+    }
+
+    fn lang_sys(&self, at: usize) -> Result<Object, BoxError> {
+        self.bytes(at, 6 + self.count(at + 4)? * 2)
+    }
+
+    /// 复制 FeatureList，删除指向已删除查找的编号
+    ///
+    /// 特性本身即使没有查找也会保留，避免重新编号 LangSys 中的特性编号
+    fn feature_list(&self, at: usize) -> Result<Object, BoxError> {
+        let mut out = Object::default();
+        let count = self.count(at)?;
+        out.u16(count as u16);
+        for i in 0..count {
+            let record = at + 2 + i * 6;
+            let feature_tag = read_u32(self.data, record)?;
+            out.u32(feature_tag);
+            let feature = self.child(at, 2 + i * 6 + 4)?;
+            let mut feature_out = Object::default();
+            feature_out.offset(
+                self.offset(feature, 0)?
+                    .map(|params| self.feature_params(feature_tag, params))
+                    .transpose()?
+                    .flatten(),
+            );
+            let mut lookups = Vec::new();
+            for l in 0..self.count(feature + 2)? {
+                if let Some(index) = self.lookup_index(self.u16(feature + 4 + l * 2)?) {
+                    lookups.push(index);
+                }
+            }
+            feature_out.u16(lookups.len() as u16);
+            for index in lookups {
+                feature_out.u16(index);
+            }
+            out.offset(Some(feature_out));
+        }
+        Ok(out)
+    }
+
+    /// 复制 FeatureParams，只认识 size、ssXX 和 cvXX 的格式
+    fn feature_params(&self, feature_tag: u32, at: usize) -> Result<Option<Object>, BoxError> {
+        let prefix = &feature_tag.to_be_bytes()[..2];
+        Ok(Some(if feature_tag == crate::sfnt::tag(b"size") {
+            self.bytes(at, 10)?
+        } else if prefix == b"ss" {
+            self.bytes(at, 4)?
+        } else if prefix == b"cv" {
+            self.bytes(at, 14 + self.count(at + 12)? * 3)?
+        } else {
+            return Ok(None);
+        }))
+    }
+
+    fn subtable(&self, kind: u16, at: usize) -> Result<Option<Object>, BoxError> {
+        match (self.gpos, kind) {
+            (false, 1) => self.single_subst(at),
+            (false, 2) => self.sequence_subst(at, true),
+            (false, 3) => self.sequence_subst(at, false),
+            (false, 4) => self.ligature_subst(at),
+            (false, 5) | (true, 7) => self.context(at),
+            (false, 6) | (true, 8) => self.chained_context(at),
+            (false, 8) => self.reverse_chained_subst(at),
+            (true, 1) => self.single_pos(at),
+            (true, 2) => self.pair_pos(at),
+            (true, 3) => self.cursive_pos(at),
+            (true, 4) | (true, 6) => self.mark_pos(at, false),
+            (true, 5) => self.mark_pos(at, true),
+            _ => Err(format!("unknown lookup type {kind}").into()),
+        }
+    }
+
+    fn single_subst(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        let format = self.u16(at)?;
+        let mut pairs = Vec::new();
+        for (i, old) in coverage(self.data, self.child(at, 2)?)?
+            .into_iter()
+            .enumerate()
+        {
+            let substitute = match format {
+                1 => old.wrapping_add(self.u16(at + 4)?),
+                2 => self.u16(at + 6 + i * 2)?,
+                _ => return Err(format!("unknown format {format}").into()),
+            };
+            if let (Some(glyph), Some(substitute)) =
+                (self.glyphs.get(old), self.glyphs.get(substitute))
+            {
+                pairs.push((glyph, substitute));
+            }
+        }
+        pairs.sort_unstable();
+        pairs.dedup_by_key(|p| p.0);
+        let Some(first) = pairs.first() else {
+            return Ok(None);
+        };
+
+        let glyphs = pairs.iter().map(|p| p.0).collect::<Vec<_>>();
+        let delta = first.1.wrapping_sub(first.0);
+        let mut out = Object::default();
+        if pairs.iter().all(|(g, s)| s.wrapping_sub(*g) == delta) {
+            out.u16(1);
+            out.offset(Some(coverage_table(&glyphs)));
+            out.u16(delta);
+        } else {
+            out.u16(2);
+            out.offset(Some(coverage_table(&glyphs)));
+            out.u16(pairs.len() as u16);
+            for (_, substitute) in pairs {
+                out.u16(substitute);
+            }
+        }
+        Ok(Some(out))
+    }
+
+    /// 多重替换和候选替换，结构相同
+    ///
+    /// 多重替换的序列中有字形不在子集中时删除整条规则，候选替换只删除该候选
+    fn sequence_subst(&self, at: usize, multiple: bool) -> Result<Option<Object>, BoxError> {
+        let mut glyphs = Vec::new();
+        let mut sets = Vec::new();
+        for (glyph, i) in self.covered(self.child(at, 2)?)? {
+            let set = self.child(at, 6 + i * 2)?;
+            let sequence = (0..self.count(set)?)
+                .map(|k| self.glyph(set + 2 + k * 2))
+                .collect::<Result<Vec<_>, _>>()?;
+            let kept = sequence.iter().flatten().copied().collect::<Vec<_>>();
+            if (multiple && kept.len() != sequence.len()) || (!multiple && kept.is_empty()) {
+                continue;
+            }
+            let mut set_out = Object::default();
+            set_out.u16(kept.len() as u16);
+            for g in kept {
+                set_out.u16(g);
+            }
+            glyphs.push(glyph);
+            sets.push(set_out);
+        }
+        Ok(with_sets(1, &glyphs, sets))
+    }
+
+    fn ligature_subst(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        let mut glyphs = Vec::new();
+        let mut sets = Vec::new();
+        for (glyph, i) in self.covered(self.child(at, 2)?)? {
+            let set = self.child(at, 6 + i * 2)?;
+            let mut ligatures = Vec::new();
+            'ligature: for l in 0..self.count(set)? {
+                let ligature = self.child(set, 2 + l * 2)?;
+                let Some(ligature_glyph) = self.glyph(ligature)? else {
+                    continue;
+                };
+                let components = self.count(ligature + 2)?;
+                let mut out = Object::default();
+                out.u16(ligature_glyph);
+                out.u16(components as u16);
+                for c in 0..components.saturating_sub(1) {
+                    match self.glyph(ligature + 4 + c * 2)? {
+                        Some(component) => out.u16(component),
+                        None => continue 'ligature,
+                    }
+                }
+                ligatures.push(out);
+            }
+            if let Some(set_out) = offsets(ligatures) {
+                glyphs.push(glyph);
+                sets.push(set_out);
+            }
+        }
+        Ok(with_sets(1, &glyphs, sets))
+    }
+
+    fn reverse_chained_subst(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        let backtrack = self.count(at + 4)?;
+        let lookahead_at = at + 6 + backtrack * 2;
+        let lookahead = self.count(lookahead_at)?;
+        let substitutes_at = lookahead_at + 2 + lookahead * 2;
+        let mut pairs = Vec::new();
+        for (glyph, i) in self.covered(self.child(at, 2)?)? {
+            if let Some(substitute) = self.glyph(substitutes_at + 2 + i * 2)? {
+                pairs.push((glyph, substitute));
+            }
+        }
+        if pairs.is_empty() {
+            return Ok(None);
+        }
+
+        let mut out = Object::default();
+        out.u16(1);
+        out.offset(Some(coverage_table(
+            &pairs.iter().map(|p| p.0).collect::<Vec<_>>(),
+        )));
+        for (count_at, count) in [(at + 4, backtrack), (lookahead_at, lookahead)] {
+            out.u16(count as u16);
+            for i in 0..count {
+                let Some(coverage) =
+                    self.coverage(at + usize::from(self.u16(count_at + 2 + i * 2)?))?
+                else {
+                    return Ok(None);
+                };
+                out.offset(Some(coverage));
+            }
+        }
+        out.u16(pairs.len() as u16);
+        for (_, substitute) in pairs {
+            out.u16(substitute);
+        }
+        Ok(Some(out))
+    }
+
+    /// 复制 SequenceLookupRecord，删除指向已删除查找的记录
+    fn lookup_records(&self, out: &mut Object, at: usize, count: usize) -> Result<(), BoxError> {
+        let mut records = Vec::new();
+        for i in 0..count {
+            if let Some(index) = self.lookup_index(self.u16(at + i * 4 + 2)?) {
+                records.push((self.u16(at + i * 4)?, index));
+            }
+        }
+        out.u16(records.len() as u16);
+        for (sequence_index, index) in records {
+            out.u16(sequence_index);
+            out.u16(index);
+        }
+        Ok(())
+    }
+
+    /// 复制序列上下文规则，glyphs 为 true 时输入序列是字形，否则是类别
+    ///
+    /// 有字形不在子集中时返回 None
+    fn rule(&self, at: usize, glyphs: bool) -> Result<Option<Object>, BoxError> {
+        let count = self.count(at)?;
+        let mut sequence = Object::default();
+        if !self.sequence(&mut sequence, at + 4, count.saturating_sub(1), glyphs)? {
+            return Ok(None);
+        }
+        let mut out = Object::default();
+        out.u16(count as u16);
+        let mut records = Object::default();
+        self.lookup_records(
+            &mut records,
+            at + 4 + count.saturating_sub(1) * 2,
+            self.count(at + 2)?,
+        )?;
+        // seqLookupCount 在输入序列之前
+        out.bytes(&records.data[..2]);
+        out.bytes(&sequence.data);
+        out.bytes(&records.data[2..]);
+        Ok(Some(out))
+    }
+
+    /// 复制链式上下文规则，glyphs 的含义和 rule 相同
+    fn chained_rule(&self, at: usize, glyphs: bool) -> Result<Option<Object>, BoxError> {
+        let mut out = Object::default();
+        let mut at = at;
+        // 输入序列不包含第一个字形
+        for skip in [0, 1, 0] {
+            let count = self.count(at)?;
+            out.u16(count as u16);
+            let len = count.saturating_sub(skip);
+            if !self.sequence(&mut out, at + 2, len, glyphs)? {
+                return Ok(None);
+            }
+            at += 2 + len * 2;
+        }
+        self.lookup_records(&mut out, at + 2, self.count(at)?)?;
+        Ok(Some(out))
+    }
+
+    /// 复制字形或类别序列，有字形不在子集中时返回 false
+    fn sequence(
+        &self,
+        out: &mut Object,
+        at: usize,
+        len: usize,
+        glyphs: bool,
+    ) -> Result<bool, BoxError> {
+        for i in 0..len {
+            let value = match glyphs {
+                true => match self.glyph(at + i * 2)? {
+                    Some(glyph) => glyph,
+                    None => return Ok(false),
+                },
+                false => self.u16(at + i * 2)?,
+            };
+            out.u16(value);
+        }
+        Ok(true)
+    }
+
+    /// 复制规则集合，没有规则剩下时返回 None
+    fn rule_set(
+        &self,
+        at: usize,
+        rule: impl Fn(usize) -> Result<Option<Object>, BoxError>,
+    ) -> Result<Option<Object>, BoxError> {
+        let mut rules = Vec::new();
+        for i in 0..self.count(at)? {
+            if let Some(rule) = rule(self.child(at, 2 + i * 2)?)? {
+                rules.push(rule);
+            }
+        }
+        Ok(offsets(rules))
+    }
+
+    /// 序列上下文（GSUB 5、GPOS 7）
+    fn context(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        match self.u16(at)? {
+            1 => {
+                let mut glyphs = Vec::new();
+                let mut sets = Vec::new();
+                for (glyph, i) in self.covered(self.child(at, 2)?)? {
+                    let Some(set) = self.offset(at, 6 + i * 2)? else {
+                        continue;
+                    };
+                    if let Some(set) = self.rule_set(set, |rule| self.rule(rule, true))? {
+                        glyphs.push(glyph);
+                        sets.push(set);
+                    }
+                }
+                Ok(with_sets(1, &glyphs, sets))
+            }
+            2 => {
+                let Some(coverage) = self.coverage(self.child(at, 2)?)? else {
+                    return Ok(None);
+                };
+                let mut out = Object::default();
+                out.u16(2);
+                out.offset(Some(coverage));
+                out.offset(Some(self.class_def(self.child(at, 4)?)?));
+                self.class_sets(&mut out, at, 6, |rule| self.rule(rule, false))?;
+                Ok(Some(out))
+            }
+            3 => {
+                let count = self.count(at + 2)?;
+                let mut out = Object::default();
+                out.u16(3);
+                out.u16(count as u16);
+                let mut records = Object::default();
+                self.lookup_records(&mut records, at + 6 + count * 2, self.count(at + 4)?)?;
+                out.bytes(&records.data[..2]);
+                for i in 0..count {
+                    let Some(coverage) = self.coverage(self.child(at, 6 + i * 2)?)? else {
+                        return Ok(None);
+                    };
+                    out.offset(Some(coverage));
+                }
+                out.bytes(&records.data[2..]);
+                Ok(Some(out))
+            }
+            format => Err(format!("unknown format {format}").into()),
+        }
+    }
+
+    /// 链式序列上下文（GSUB 6、GPOS 8）
+    fn chained_context(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        match self.u16(at)? {
+            1 => {
+                let mut glyphs = Vec::new();
+                let mut sets = Vec::new();
+                for (glyph, i) in self.covered(self.child(at, 2)?)? {
+                    let Some(set) = self.offset(at, 6 + i * 2)? else {
+                        continue;
+                    };
+                    if let Some(set) = self.rule_set(set, |rule| self.chained_rule(rule, true))? {
+                        glyphs.push(glyph);
+                        sets.push(set);
+                    }
+                }
+                Ok(with_sets(1, &glyphs, sets))
+            }
+            2 => {
+                let Some(coverage) = self.coverage(self.child(at, 2)?)? else {
+                    return Ok(None);
+                };
+                let mut out = Object::default();
+                out.u16(2);
+                out.offset(Some(coverage));
+                for field in [4, 6, 8] {
+                    out.offset(
+                        self.offset(at, field)?
+                            .map(|at| self.class_def(at))
+                            .transpose()?,
+                    );
+                }
+                self.class_sets(&mut out, at, 10, |rule| self.chained_rule(rule, false))?;
+                Ok(Some(out))
+            }
+            3 => {
+                let mut out = Object::default();
+                out.u16(3);
+                let mut count_at = at + 2;
+                for _ in 0..3 {
+                    let count = self.count(count_at)?;
+                    out.u16(count as u16);
+                    for i in 0..count {
+                        let coverage = at + usize::from(self.u16(count_at + 2 + i * 2)?);
+                        let Some(coverage) = self.coverage(coverage)? else {
+                            return Ok(None);
+                        };
+                        out.offset(Some(coverage));
+                    }
+                    count_at += 2 + count * 2;
+                }
+                self.lookup_records(&mut out, count_at + 2, self.count(count_at)?)?;
+                Ok(Some(out))
+            }
+            format => Err(format!("unknown format {format}").into()),
+        }
+    }
+
+    /// 复制按类别索引的规则集合，类别不会重新编号，空集合写为空偏移
+    fn class_sets(
+        &self,
+        out: &mut Object,
+        at: usize,
+        field: usize,
+        rule: impl Fn(usize) -> Result<Option<Object>, BoxError>,
+    ) -> Result<(), BoxError> {
+        let count = self.count(at + field)?;
+        out.u16(count as u16);
+        for i in 0..count {
+            let set = match self.offset(at, field + 2 + i * 2)? {
+                Some(set) => self.rule_set(set, &rule)?,
+                None => None,
+            };
+            out.offset(set);
+        }
+        Ok(())
+    }
+
+    /// 复制 ValueRecord，设备表的偏移相对 base
+    fn value_record(
+        &self,
+        out: &mut Object,
+        format: u16,
+        base: usize,
+        at: usize,
+    ) -> Result<(), BoxError> {
+        let mut at = at;
+        for bit in 0..8 {
+            if format & (1 << bit) == 0 {
+                continue;
+            }
+            let value = self.u16(at)?;
+            at += 2;
+            match bit {
+                0..4 => out.u16(value),
+                _ => out.offset(match value {
+                    0 => None,
+                    _ => self.device(base + usize::from(value))?,
+                }),
+            }
+        }
+        Ok(())
+    }
+
+    /// 复制 Device 表，VariationIndex 表对应的变体数据不会保留，返回 None
+    fn device(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        let (start, end) = (self.u16(at)?, self.u16(at + 2)?);
+        let bits = match self.u16(at + 4)? {
+            1 => 2,
+            2 => 4,
+            3 => 8,
+            _ => return Ok(None),
+        };
+        let sizes = usize::from(end.saturating_sub(start)) + 1;
+        Ok(Some(self.bytes(at, 6 + (sizes * bits).div_ceil(16) * 2)?))
+    }
+
+    /// 复制 Anchor 表
+    fn anchor(&self, at: usize) -> Result<Object, BoxError> {
+        match self.u16(at)? {
+            1 => self.bytes(at, 6),
+            2 => self.bytes(at, 8),
+            3 => {
+                let mut out = self.bytes(at, 6)?;
+                for field in [6, 8] {
+                    out.offset(match self.offset(at, field)? {
+                        Some(device) => self.device(device)?,
+                        None => None,
+                    });
+                }
+                Ok(out)
+            }
+            format => Err(format!("unknown anchor format {format}").into()),
+        }
+    }
+
+    /// 可以为空的 Anchor 表偏移
+    fn anchor_at(&self, base: usize, field: usize) -> Result<Option<Object>, BoxError> {
+        self.offset(base, field)?
+            .map(|at| self.anchor(at))
+            .transpose()
+    }
+
+    fn single_pos(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        let format = self.u16(at)?;
+        let value_format = self.u16(at + 4)?;
+        let covered = self.covered(self.child(at, 2)?)?;
+        if covered.is_empty() {
+            return Ok(None);
+        }
+        let glyphs = covered.iter().map(|c| c.0).collect::<Vec<_>>();
+        let mut out = Object::default();
+        out.u16(format);
+        out.offset(Some(coverage_table(&glyphs)));
+        out.u16(value_format);
+        match format {
+            1 => self.value_record(&mut out, value_format, at, at + 6)?,
+            2 => {
+                let size = value_size(value_format);
+                out.u16(covered.len() as u16);
+                for (_, i) in covered {
+                    self.value_record(&mut out, value_format, at, at + 8 + i * size)?;
+                }
+            }
+            format => return Err(format!("unknown format {format}").into()),
+        }
+        Ok(Some(out))
+    }
+
+    fn pair_pos(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        let format = self.u16(at)?;
+        let (format1, format2) = (self.u16(at + 4)?, self.u16(at + 6)?);
+        let (size1, size2) = (value_size(format1), value_size(format2));
+        let mut out = Object::default();
+        match format {
+            1 => {
+                let mut glyphs = Vec::new();
+                let mut sets = Vec::new();
+                for (glyph, i) in self.covered(self.child(at, 2)?)? {
+                    let set = self.child(at, 10 + i * 2)?;
+                    let record_size = 2 + size1 + size2;
+                    let mut pairs = Vec::new();
+                    for p in 0..self.count(set)? {
+                        let record = set + 2 + p * record_size;
+                        if let Some(second) = self.glyph(record)? {
+                            pairs.push((second, record));
+                        }
+                    }
+                    pairs.sort_unstable();
+                    pairs.dedup_by_key(|p| p.0);
+                    if pairs.is_empty() {
+                        continue;
+                    }
+                    let mut set_out = Object::default();
+                    set_out.u16(pairs.len() as u16);
+                    for (second, record) in pairs {
+                        set_out.u16(second);
+                        self.value_record(&mut set_out, format1, set, record + 2)?;
+                        self.value_record(&mut set_out, format2, set, record + 2 + size1)?;
+                    }
+                    glyphs.push(glyph);
+                    sets.push(set_out);
+                }
+                if glyphs.is_empty() {
+                    return Ok(None);
+                }
+                out.u16(1);
+                out.offset(Some(coverage_table(&glyphs)));
+                out.u16(format1);
+                out.u16(format2);
+                out.u16(sets.len() as u16);
+                for set in sets {
+                    out.offset(Some(set));
+                }
+            }
+            2 => {
+                let covered = self.covered(self.child(at, 2)?)?;
+                if covered.is_empty() {
+                    return Ok(None);
+                }
+                // 只保留子集字形用到的类别，类别 0 始终保留
+                let mut classes1 = self.classes(self.child(at, 8)?)?;
+                let glyphs = covered.iter().map(|c| c.0).collect::<Vec<_>>();
+                classes1.retain(|c| glyphs.binary_search(&c.0).is_ok());
+                let mut classes2 = self.classes(self.child(at, 10)?)?;
+                let (used1, used2) = (renumber(&mut classes1), renumber(&mut classes2));
+                let class2_count = self.count(at + 14)?;
+                let row_size = class2_count * (size1 + size2);
+
+                out.u16(2);
+                out.offset(Some(coverage_table(&glyphs)));
+                out.u16(format1);
+                out.u16(format2);
+                out.offset(Some(class_def_table(&classes1)));
+                out.offset(Some(class_def_table(&classes2)));
+                out.u16(used1.len() as u16);
+                out.u16(used2.len() as u16);
+                for class1 in used1 {
+                    for class2 in &used2 {
+                        let record = at
+                            + 16
+                            + usize::from(class1) * row_size
+                            + usize::from(*class2) * (size1 + size2);
+                        self.value_record(&mut out, format1, at, record)?;
+                        self.value_record(&mut out, format2, at, record + size1)?;
+                    }
+                }
+            }
+            format => return Err(format!("unknown format {format}").into()),
+        }
+        Ok(Some(out))
+    }
+
+    fn cursive_pos(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        let covered = self.covered(self.child(at, 2)?)?;
+        if covered.is_empty() {
+            return Ok(None);
+        }
+        let mut out = Object::default();
+        out.u16(1);
+        out.offset(Some(coverage_table(
+            &covered.iter().map(|c| c.0).collect::<Vec<_>>(),
+        )));
+        out.u16(covered.len() as u16);
+        for (_, i) in covered {
+            out.offset(self.anchor_at(at, 6 + i * 4)?);
+            out.offset(self.anchor_at(at, 8 + i * 4)?);
+        }
+        Ok(Some(out))
+    }
+
+    /// 标记附着（GPOS 4、5、6），ligature 为 true 时是附着到连字
+    fn mark_pos(&self, at: usize, ligature: bool) -> Result<Option<Object>, BoxError> {
+        let marks = self.covered(self.child(at, 2)?)?;
+        let bases = self.covered(self.child(at, 4)?)?;
+        if marks.is_empty() || bases.is_empty() {
+            return Ok(None);
+        }
+        let class_count = self.count(at + 6)?;
+        let mark_array = self.child(at, 8)?;
+        let base_array = self.child(at, 10)?;
+
+        // 只保留子集中标记用到的类别
+        let mut used = Vec::new();
+        for (_, i) in &marks {
+            let class = self.u16(mark_array + 2 + i * 4)?;
+            if usize::from(class) < class_count && !used.contains(&class) {
+                used.push(class);
+            }
+        }
+        used.sort_unstable();
+
+        let mut marks_out = Object::default();
+        marks_out.u16(marks.len() as u16);
+        for (_, i) in &marks {
+            let class = self.u16(mark_array + 2 + i * 4)?;
+            marks_out.u16(used.iter().position(|c| *c == class).unwrap_or_default() as u16);
+            marks_out.offset(Some(self.anchor(self.child(mark_array, 4 + i * 4)?)?));
+        }
+
+        let mut bases_out = Object::default();
+        bases_out.u16(bases.len() as u16);
+        for (_, i) in &bases {
+            if ligature {
+                let attach = self.child(base_array, 2 + i * 2)?;
+                let components = self.count(attach)?;
+                let mut attach_out = Object::default();
+                attach_out.u16(components as u16);
+                for c in 0..components {
+                    for class in &used {
+                        let field = 2 + (c * class_count + usize::from(*class)) * 2;
+                        attach_out.offset(self.anchor_at(attach, field)?);
+                    }
+                }
+                bases_out.offset(Some(attach_out));
+            } else {
+                for class in &used {
+                    let field = 2 + (i * class_count + usize::from(*class)) * 2;
+                    bases_out.offset(self.anchor_at(base_array, field)?);
+                }
+            }
+        }
+
+        let mut out = Object::default();
+        out.u16(1);
+        out.offset(Some(coverage_table(
+            &marks.iter().map(|c| c.0).collect::<Vec<_>>(),
+        )));
+        out.offset(Some(coverage_table(
+            &bases.iter().map(|c| c.0).collect::<Vec<_>>(),
+        )));
+        out.u16(used.len() as u16);
+        out.offset(Some(marks_out));
+        out.offset(Some(bases_out));
+        Ok(Some(out))
+    }
+
+    /// GDEF 的 AttachList
+    fn attach_list(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        let covered = self.covered(self.child(at, 0)?)?;
+        let mut points = Vec::new();
+        for (_, i) in &covered {
+            let point = self.child(at, 4 + i * 2)?;
+            points.push(self.bytes(point, 2 + self.count(point)? * 2)?);
+        }
+        let glyphs = covered.iter().map(|c| c.0).collect::<Vec<_>>();
+        Ok(covered_tables(&glyphs, points))
+    }
+
+    /// GDEF 的 LigCaretList
+    fn lig_caret_list(&self, at: usize) -> Result<Option<Object>, BoxError> {
+        let covered = self.covered(self.child(at, 0)?)?;
+        let mut ligatures = Vec::new();
+        for (_, i) in &covered {
+            let ligature = self.child(at, 4 + i * 2)?;
+            let mut out = Object::default();
+            let count = self.count(ligature)?;
+            out.u16(count as u16);
+            for c in 0..count {
+                let caret = self.child(ligature, 2 + c * 2)?;
+                let caret_out = match self.u16(caret)? {
+                    1 | 2 => self.bytes(caret, 4)?,
+                    3 => {
+                        let mut caret_out = self.bytes(caret, 4)?;
+                        caret_out.offset(match self.offset(caret, 4)? {
+                            Some(device) => self.device(device)?,
+                            None => None,
+                        });
+                        caret_out
+                    }
+                    format => return Err(format!("unknown caret format {format}").into()),
+                };
+                out.offset(Some(caret_out));
+            }
+            ligatures.push(out);
+        }
+        let glyphs = covered.iter().map(|c| c.0).collect::<Vec<_>>();
+        Ok(covered_tables(&glyphs, ligatures))
+    }
+
+    /// GDEF 的 MarkGlyphSets，集合编号被查找引用，空集合也会保留
+    fn mark_glyph_sets(&self, at: usize) -> Result<Object, BoxError> {
+        let count = self.count(at + 2)?;
+        let mut out = Object::default();
+        out.u16(1);
+        out.u16(count as u16);
+        for i in 0..count {
+            let coverage = at + read_u32(self.data, at + 4 + i * 4)? as usize;
+            out.offset32(Some(
+                self.coverage(coverage)?
+                    .unwrap_or_else(|| coverage_table(&[])),
+            ));
+        }
+        Ok(out)
+    }
+}
+
+/// 把类别重新编号为连续的值，返回新类别对应的原类别，类别 0 始终在第一个
+fn renumber(classes: &mut [(u16, u16)]) -> Vec<u16> {
+    let mut used = vec![0];
+    used.extend(classes.iter().map(|c| c.1).collect::<BTreeSet<_>>());
+    for class in classes.iter_mut() {
+        class.1 = used.iter().position(|c| *c == class.1).unwrap_or_default() as u16;
+    }
+    used
+}
+
+/// ValueRecord 的字节数
+fn value_size(format: u16) -> usize {
+    (format & 0xFF).count_ones() as usize * 2
+}
+
+/// 生成 format、Coverage 偏移、数量和子表偏移组成的子表，没有子表时返回 None
+fn with_sets(format: u16, glyphs: &[u16], sets: Vec<Object>) -> Option<Object> {
+    let mut out = Object::default();
+    out.u16(format);
+    out.append(covered_tables(glyphs, sets)?);
+    Some(out)
+}
+
+/// Coverage 偏移、数量和子表偏移组成的表，没有子表时返回 None
+fn covered_tables(glyphs: &[u16], tables: Vec<Object>) -> Option<Object> {
+    if glyphs.is_empty() {
+        return None;
+    }
+    let mut out = Object::default();
+    out.offset(Some(coverage_table(glyphs)));
+    out.u16(tables.len() as u16);
+    for table in tables {
+        out.offset(Some(table));
+    }
+    Some(out)
+}
+
+/// 由数量和偏移数组组成的表，没有子表时返回 None
+fn offsets(tables: Vec<Object>) -> Option<Object> {
+    if tables.is_empty() {
+        return None;
+    }
+    let mut out = Object::default();
+    out.u16(tables.len() as u16);
+    for table in tables {
+        out.offset(Some(table));
+    }
+    Some(out)
+}
+
+/// 待写入的表，偏移字段在排列后填写
+#[derive(Default, Clone)]
+struct Object {
+    data: Vec<u8>,
+    links: Vec<Link>,
+}
+
+/// 表中的偏移字段
+#[derive(Clone)]
+struct Link {
+    /// 偏移字段在 data 中的位置
+    pos: usize,
+    wide: bool,
+    target: Object,
+}
+
+impl Object {
+    fn u16(&mut self, v: u16) {
+        self.data.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.data.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn bytes(&mut self, data: &[u8]) {
+        self.data.extend_from_slice(data);
+    }
+
+    /// 在末尾接上另一个表的内容
+    fn append(&mut self, other: Object) {
+        let base = self.data.len();
+        self.data.extend(other.data);
+        self.links.extend(other.links.into_iter().map(|l| Link {
+            pos: base + l.pos,
+            ..l
+        }));
+    }
+
+    /// 16 位偏移，None 时写入空偏移
+    fn offset(&mut self, target: Option<Object>) {
+        self.link(target, false);
+    }
+
+    /// 32 位偏移，None 时写入空偏移
+    fn offset32(&mut self, target: Option<Object>) {
+        self.link(target, true);
+    }
+
+    fn link(&mut self, target: Option<Object>, wide: bool) {
+        let pos = self.data.len();
+        self.data.resize(pos + if wide { 4 } else { 2 }, 0);
+        if let Some(target) = target {
+            self.links.push(Link { pos, wide, target });
+        }
+    }
+}
+
+/// 去重后的表，偏移指向的表用编号表示
+#[derive(PartialEq, Eq, Hash, Clone)]
+struct Node {
+    data: Vec<u8>,
+    /// (偏移字段位置, 32 位偏移, 指向的表)
+    links: Vec<(usize, bool, usize)>,
+}
+
+/// 把表按内容去重后依次写出，偏移溢出时返回 None
+///
+/// 每个表都写在引用它的表之后；32 位偏移指向的表连同其 16 位偏移引用的表单独成组写在
+/// 后面，以免扩展查找的子表占用 LookupList 附近的 16 位偏移空间
+fn pack(root: Object) -> Option<Vec<u8>> {
+    let mut nodes = Vec::new();
+    let mut ids = HashMap::new();
+    let root = intern(root, &mut nodes, &mut ids);
+
+    let mut out = Vec::new();
+    // 各组起始表的位置
+    let mut groups = HashMap::new();
+    // (偏移字段位置, 所在表的位置, 指向的组)
+    let mut wide_links = Vec::new();
+    let mut queue = VecDeque::from([root]);
+    while let Some(group) = queue.pop_front() {
+        if groups.contains_key(&group) {
+            continue;
+        }
+        // 组之间不共用表，同一个表在每组中各写一次
+        let mut positions = HashMap::new();
+        let order = group_order(&nodes, group);
+        for id in &order {
+            positions.insert(*id, out.len());
+            out.extend_from_slice(&nodes[*id].data);
+            // 保持 2 字节对齐
+            if out.len() % 2 != 0 {
+                out.push(0);
+            }
+        }
+        for id in order {
+            let parent = positions[&id];
+            for (pos, wide, target) in &nodes[id].links {
+                if *wide {
+                    wide_links.push((parent + pos, parent, *target));
+                    queue.push_back(*target);
+                } else {
+                    let offset = u16::try_from(positions[target] - parent).ok()?;
+                    out[parent + pos..parent + pos + 2].copy_from_slice(&offset.to_be_bytes());
+                }
+            }
+        }
+        groups.insert(group, positions[&group]);
+    }
+    for (at, parent, target) in wide_links {
+        let offset = u32::try_from(groups[&target].checked_sub(parent)?).ok()?;
+        out[at..at + 4].copy_from_slice(&offset.to_be_bytes());
+    }
+    Some(out)
+}
+
+/// 保存表并返回编号，内容和引用都相同的表只保存一次
+fn intern(object: Object, nodes: &mut Vec<Node>, ids: &mut HashMap<Node, usize>) -> usize {
+    let links = object
+        .links
+        .into_iter()
+        .map(|l| (l.pos, l.wide, intern(l.target, nodes, ids)))
+        .collect();
+    let node = Node {
+        data: object.data,
+        links,
+    };
+    *ids.entry(node.clone()).or_insert_with(|| {
+        nodes.push(node);
+        nodes.len() - 1
+    })
+}
+
+/// 从 root 出发经 16 位偏移能到达的表，按拓扑顺序排列，被引用的表在引用它的表之后
+fn group_order(nodes: &[Node], root: usize) -> Vec<usize> {
+    let narrow = |id: usize| {
+        nodes[id]
+            .links
+            .iter()
+            .filter(|l| !l.1)
+            .map(|l| l.2)
+            .collect::<Vec<_>>()
+    };
+    let mut members = vec![root];
+    let mut seen = HashSet::from([root]);
+    let mut i = 0;
+    while i < members.len() {
+        for target in narrow(members[i]) {
+            if seen.insert(target) {
+                members.push(target);
+            }
+        }
+        i += 1;
+    }
+    let mut parents = HashMap::<usize, usize>::new();
+    for member in &members {
+        for target in narrow(*member) {
+            *parents.entry(target).or_default() += 1;
+        }
+    }
+    let mut order = Vec::with_capacity(members.len());
+    let mut queue = VecDeque::from([root]);
+    while let Some(id) = queue.pop_front() {
+        order.push(id);
+        for target in narrow(id) {
+            let count = parents.get_mut(&target).expect("counted above");
+            *count -= 1;
+            if *count == 0 {
+                queue.push_back(target);
+            }
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(values: &[u16]) -> Object {
+        let mut out = Object::default();
+        for v in values {
+            out.u16(*v);
+        }
+        out
+    }
+
+    #[test]
+    fn pack_shares_identical_tables() {
+        let mut root = table(&[2]);
+        root.offset(Some(table(&[1, 2, 3])));
+        root.offset(Some(table(&[1, 2, 3])));
+        root.offset(None);
+        let packed = pack(root).unwrap();
+        assert_eq!(packed.len(), 8 + 6);
+        assert_eq!(read_u16(&packed, 2).unwrap(), 8);
+        assert_eq!(read_u16(&packed, 4).unwrap(), 8);
+        assert_eq!(read_u16(&packed, 6).unwrap(), 0);
+    }
+
+    #[test]
+    fn pack_fails_on_offset_overflow() {
+        let mut root = Object::default();
+        root.offset(Some(table(&[0; 0x8000])));
+        root.offset(Some(table(&[1])));
+        assert!(pack(root.clone()).is_none());
+
+        // 32 位偏移指向的表不受限制
+        let mut wide = Object::default();
+        wide.offset32(Some(root.links[0].target.clone()));
+        wide.offset32(Some(table(&[1])));
+        let packed = pack(wide).unwrap();
+        assert_eq!(read_u32(&packed, 4).unwrap(), 8 + 0x10000);
+    }
+}
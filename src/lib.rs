@@ -0,0 +1,75 @@
+//! 字体子集化库，`fontview` 命令行和界面都基于这里的实现
+//!
+//! 可以在 build.rs 或者其他服务中直接调用：
+//!
+//! ```no_run
+//! use fontview::font_info::{self, OutputFormat, SubsetOptions};
+//!
+//! let data = std::fs::read("MiSans-Thin.otf")?;
+//! let font = font_info::load(&data)?;
+//! let face = font.table_provider(0)?;
+//! let names = font_info::names(&face)?;
+//! println!("{} {}", names.family, names.subfamily);
+//!
+//! let options = SubsetOptions {
+//!     format: OutputFormat::Woff2,
+//!     ..Default::default()
+//! };
+//! let report = font_info::subset_text(&face, "fontview", &"out.woff2".into(), &options)?;
+//! println!("{}", report.family);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! s_error {
+    // debug!(target: "my_target", key1 = 42, key2 = true; "a {} event", "log")
+    // debug!(target: "my_target", "a {} event", "log")
+    // (target: $target:expr, $($arg:tt)+) => (log!(target: $target, $crate::Level::Debug, $($arg)+));
+
+    // debug!("a {} event", "log")
+    ($($arg:tt)+) => (
+        #[cfg(feature="print")]
+        log::error!($($arg)+);
+        // 避免clippy或者ide认为参数没有被使用
+        #[cfg(not(feature="print"))]
+        let _ = format!($($arg)+);
+    )
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! s_info {
+    // debug!(target: "my_target", key1 = 42, key2 = true; "a {} event", "log")
+    // debug!(target: "my_target", "a {} event", "log")
+    // (target: $target:expr, $($arg:tt)+) => (log!(target: $target, $crate::Level::Debug, $($arg)+));
+
+    // debug!("a {} event", "log")
+    ($($arg:tt)+) => {{
+        #[cfg(feature="print")]
+        log::info!($($arg)+);
+        // 避免clippy或者ide认为参数没有被使用
+        #[cfg(not(feature="print"))]
+        let _ = format!($($arg)+);
+    }};
+    () => (
+
+        #[cfg(feature="print")]
+        log::info!("");
+    )
+}
+
+pub mod blocks;
+pub mod charset;
+mod chunk;
+pub mod corpus;
+pub mod font_info;
+mod gsub;
+pub mod inspect;
+mod language;
+mod layout;
+mod rename;
+mod sfnt;
+mod uvs;
+mod vertical;
+mod woff;
@@ -0,0 +1,138 @@
+//! `fontview cli list`，列出目录下所有字体
+
+use std::path::Path;
+
+use fontview::corpus;
+use fontview::font_info::{self, FontError};
+use fontview::inspect::Container;
+use serde_json::json;
+
+/// 字体文件的后缀
+const EXTENSIONS: &[&str] = &["ttf", "otf", "ttc", "otc", "woff", "woff2"];
+
+/// 输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ListFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+/// 字体集合中的一个字体
+struct Row {
+    /// 相对目录的路径
+    file: String,
+    face: usize,
+    family: String,
+    style: String,
+    format: Container,
+    glyphs: u16,
+    /// 文件大小
+    size: u64,
+}
+
+/// 递归列出目录下的字体，每个字体一行，按路径排序
+///
+/// 无法解析的文件输出到标准错误并跳过
+pub(crate) fn run(dir: &Path, format: ListFormat) -> Result<(), FontError> {
+    let files = corpus::files(dir).map_err(|e| FontError::io(dir, e))?;
+    let mut rows = Vec::new();
+    for (relative, path) in files {
+        let is_font = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .is_some_and(|e| EXTENSIONS.contains(&e.as_str()));
+        if !is_font {
+            continue;
+        }
+        if let Err(e) = read_faces(&relative, &path, &mut rows) {
+            eprintln!("{}: {}", path.display(), e);
+        }
+    }
+
+    match format {
+        ListFormat::Table => crate::batch::print_table(
+            &[
+                "FILE", "FACE", "FAMILY", "STYLE", "FORMAT", "GLYPHS", "SIZE",
+            ],
+            &rows
+                .iter()
+                .map(|r| {
+                    [
+                        r.file.clone(),
+                        r.face.to_string(),
+                        r.family.clone(),
+                        r.style.clone(),
+                        r.format.to_string(),
+                        r.glyphs.to_string(),
+                        r.size.to_string(),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ),
+        ListFormat::Json => {
+            let fonts = rows
+                .iter()
+                .map(|r| {
+                    json!({
+                        "file": r.file,
+                        "face": r.face,
+                        "family": r.family,
+                        "style": r.style,
+                        "format": r.format.to_string(),
+                        "glyphs": r.glyphs,
+                        "size": r.size,
+                    })
+                })
+                .collect::<Vec<_>>();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&fonts).unwrap_or_default()
+            );
+        }
+        ListFormat::Csv => {
+            println!("file,face,family,style,format,glyphs,size");
+            for r in rows.iter() {
+                println!(
+                    "{},{},{},{},{},{},{}",
+                    csv_field(&r.file),
+                    r.face,
+                    csv_field(&r.family),
+                    csv_field(&r.style),
+                    r.format,
+                    r.glyphs,
+                    r.size
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_faces(relative: &str, path: &Path, rows: &mut Vec<Row>) -> Result<(), FontError> {
+    let data = std::fs::read(path).map_err(|e| FontError::io(path, e))?;
+    let font = font_info::load(&data)?;
+    for face in 0..font_info::face_count(&data) {
+        let provider = font.table_provider(face)?;
+        let names = font_info::names(&provider).unwrap_or_default();
+        rows.push(Row {
+            file: relative.to_string(),
+            face,
+            family: names.family,
+            style: names.subfamily,
+            format: Container::detect(&data),
+            glyphs: font_info::glyph_count(&provider)?,
+            size: data.len() as u64,
+        });
+    }
+    Ok(())
+}
+
+/// 包含逗号、引号或换行的字段需要用引号包围，引号写两次
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
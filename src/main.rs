@@ -23,46 +23,21 @@ use std::{
     time::Duration,
 };
 
-use crate::font_info::dump;
-
-macro_rules! s_error {
-    // debug!(target: "my_target", key1 = 42, key2 = true; "a {} event", "log")
-    // debug!(target: "my_target", "a {} event", "log")
-    // (target: $target:expr, $($arg:tt)+) => (log!(target: $target, $crate::Level::Debug, $($arg)+));
-
-    // debug!("a {} event", "log")
-    ($($arg:tt)+) => (
-        #[cfg(feature="print")]
-        log::error!($($arg)+);
-        // 避免clippy或者ide认为参数没有被使用
-        #[cfg(not(feature="print"))]
-        let _ = format!($($arg)+);
-    )
-}
-
-macro_rules! s_info {
-    // debug!(target: "my_target", key1 = 42, key2 = true; "a {} event", "log")
-    // debug!(target: "my_target", "a {} event", "log")
-    // (target: $target:expr, $($arg:tt)+) => (log!(target: $target, $crate::Level::Debug, $($arg)+));
-
-    // debug!("a {} event", "log")
-    ($($arg:tt)+) => {{
-        #[cfg(feature="print")]
-        log::info!($($arg)+);
-        // 避免clippy或者ide认为参数没有被使用
-        #[cfg(not(feature="print"))]
-        let _ = format!($($arg)+);
-    }};
-    () => (
-
-        #[cfg(feature="print")]
-        log::info!("");
-    )
-}
-
+use fontview::font_info::{self, FontError, dump};
+use fontview::inspect::{Container, DisplayLanguage, FaceInfo};
+use fontview::{charset, corpus, inspect};
+
+mod batch;
+mod check;
+mod config;
+mod fallback;
+mod info;
+mod list;
+mod report;
 
 /// bundle内执行方法
 mod bundle {
+    use fontview::s_info;
 
     /// 检查是否在Bundle环境中运行
     pub(super) fn is_bundle_environment() -> bool {
@@ -90,11 +65,13 @@ mod bundle {
         None
     }
 }
-#[cfg(debug_assertions)]
 mod custom_log {
 
-    use std::{io::Write, time::Duration};
+    #[cfg(debug_assertions)]
+    use std::io::Write;
+    use std::time::Duration;
     /// 时间戳转换，从1970年开始
+    #[cfg(debug_assertions)]
     pub(crate) fn time_display(value: u64) -> String {
         do_time_display(value, 1970, Duration::from_secs(8 * 60 * 60))
     }
@@ -173,6 +150,7 @@ mod custom_log {
     /// 例如：
     /// 2023-09-28T09:32:24Z
     ///
+    #[cfg(debug_assertions)]
     pub(crate) fn time_format() -> String {
         // 获取当前时间戳
         let time = std::time::SystemTime::now()
@@ -182,10 +160,12 @@ mod custom_log {
 
         time_display(time)
     }
+    #[cfg(debug_assertions)]
     struct Writer {
         console: std::io::Stdout,
         fs: Option<std::fs::File>,
     }
+    #[cfg(debug_assertions)]
     impl Writer {
         pub fn new() -> Self {
             Writer {
@@ -194,6 +174,7 @@ mod custom_log {
             }
         }
     }
+    #[cfg(debug_assertions)]
     impl Write for Writer {
         fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
             if let Some(fs) = &mut self.fs {
@@ -213,6 +194,7 @@ mod custom_log {
             }
         }
     }
+    #[cfg(debug_assertions)]
     pub(crate) fn init() -> Result<(), String> {
         // if opt.verbose {
         //     std::env::set_var("RUST_LOG", "debug");
@@ -244,7 +226,7 @@ fn icon_data() -> Vec<u8> {
         if icon.exists()
             && let Ok(v) = std::fs::read(icon)
         {
-            s_info!("bytes = {:?}", &v[0..10]);
+            fontview::s_info!("bytes = {:?}", &v[0..10]);
             return v;
         }
     }
@@ -270,49 +252,408 @@ fn main() -> eframe::Result {
             let help: &str = "\
 USAGE:
   fontview cli --input PATH --output PATH [OPTIONS]
+  fontview cli --input PATH --input GLOB ... --output TEMPLATE [OPTIONS]
+  fontview cli --config fontview.toml [--force]
+  fontview cli check --font PATH [--face INDEX|NAME] --text String ...
+  fontview cli info FONT [--json]
+  fontview cli list DIR [--json | --csv]
 
 FLAGS:
   -h, --help            Prints help information
 
+COMMANDS:
+  check                 Prints Characters Missing From --font, Grouped By Unicode Block,
+                        Exits With Code 2 If Any Is Missing. Text Is Given By --text, --file,
+                        --corpus, --unicodes, --unicodes-file Or --charset
+  info FONT             Prints Container, Faces, Tables, Metrics And Names Of A Font,
+                        --json For JSON Output
+  list DIR              Prints File, Family, Style, Format, Glyphs And Size Of Every Font
+                        Face Under DIR, --json Or --csv For Machine-Readable Output
+
 OPTIONS:
-  --input PATH          Font File
-  --output PATH         Output Path
+  --input PATH          Font File, Repeatable, Also Accepts A Glob e.g. 'fonts/*.otf'
+  --output PATH         Output Path, With Several Inputs A Directory Or A Template Using
+                        {family} {subfamily} {stem} {ext} {index}, e.g. 'out/{stem}.subset.{ext}'
+  --jobs N              Fonts Subset In Parallel (default: number of CPUs)
+  --config PATH         Run The Subset Jobs In A TOML File, Unchanged Jobs Are Skipped
+  --force               With --config, Run Every Job Even If Nothing Changed
   --text String         Used Text
   --file PATH           Read Used Text From File
+  --unicodes RANGES     Unicode Ranges, e.g. U+0000-00FF,U+3000-303F,U+4E??
+  --unicodes-file PATH  Read Unicode Ranges From File, '#' Starts A Comment
+  --charset NAMES       Built-in Charsets, e.g. gb2312-level1,ascii,cjk-punctuation
+  --list-charsets       Prints Built-in Charsets
+  --corpus DIR          Extract Used Text From Source Files Under DIR
+  --include GLOB        Files Of The Corpus To Read, Repeatable, e.g. '**/*.html'
+  --exclude GLOB        Files Of The Corpus To Skip, Repeatable
+                        (node_modules, .git and target are always skipped)
+  --format FORMAT       Output Format: ttf, otf, woff, woff2 (default: by output extension)
+  --face INDEX|NAME     Face Of A Font Collection (default: 0)
+  --all-faces           Subset Every Face Of A Font Collection, One File Per Face
+  --collection          With --all-faces, Write A Single Font Collection Instead
+  --profile PROFILE     Tables To Keep: minimal, web (default: minimal)
+                        web keeps hinting, gasp, vertical metrics
+                        (vhea, vmtx, VORG) and layout tables
+                        (GDEF, GSUB, GPOS, kern)
+  --keep-tables TAGS    Extra Tables To Keep, e.g. GPOS,kern
+  --drop-tables TAGS    Tables To Drop From The Profile, e.g. gasp
+  --features TAGS       GSUB Features Whose Ligatures And Alternates Are Kept
+                        When GSUB Is Kept, e.g. liga,vert,locl (default: all)
+  --family NAME         Family Name Of The Subset Font (default: random)
+  --seed NUMBER         Seed Of The Random Family Name
+  --deterministic       Derive The Random Family Name From The Font And Text,
+                        Same Input Always Gives Byte-Identical Output
+  --strict              Exit With Code 2 If Any Character Is Missing From The Font
+  --fallback            Treat The Inputs As A Fallback Chain In Order, Each Character Goes To
+                        The First Font Having It, Writes One Subset Per Font With Only Its
+                        Characters And Prints A Coverage Table
+  --chunks N            Split The Font Into N Chunks By unicode-range, Most Used Characters First,
+                        Writes out-0.woff2, out-1.woff2 ... And A CSS With One @font-face Per Chunk
+  --chunk-order PATH    Characters In This File Come First, In Order Of Appearance
+  --css PATH            CSS Output Path Of --chunks (default: output with .css extension)
+  --report FORMAT       Print A Machine-Readable Report Instead Of Names: json
+
+EXIT CODES:
+  0  Success
+  2  Characters Missing From The Font With --strict
+  3  Invalid Arguments
+  4  Read File Fail
+  5  Parse Font Fail
+  6  Unsupported Format
+  7  No Glyphs Left In The Subset
+  8  Write Output Fail
 ";
             println!("{}", help);
+            return Ok(());
         };
 
-        let input: String = pargs.value_from_str("--input").expect("--input err");
-        let output: String = pargs.value_from_str("--output").expect("--output err");
+        if pargs.contains("--list-charsets") {
+            for c in charset::CHARSETS {
+                println!("{:<20}{}", c.name, c.description);
+            }
+            return Ok(());
+        }
 
-        let font = std::fs::read(input).expect("load font fail");
+        match pargs.subcommand().unwrap_or_else(arg_error).as_deref() {
+            Some("info") => {
+                let json = pargs.contains("--json");
+                let path: String = pargs.free_from_str().unwrap_or_else(arg_error);
+                if let Err(e) = info::run(std::path::Path::new(&path), json) {
+                    fail(e);
+                }
+                return Ok(());
+            }
+            Some("check") => {
+                let font: String = pargs.value_from_str("--font").unwrap_or_else(arg_error);
+                let face: Option<String> =
+                    pargs.opt_value_from_str("--face").unwrap_or_else(arg_error);
+                let (text, range_text) = read_sources(&mut pargs);
+                if text.is_none() && range_text.is_empty() {
+                    fail(FontError::Usage(REQUIRE_TEXT.to_string()));
+                }
+                let text = text.unwrap_or_default() + &range_text;
+                if let Err(e) = check::run(std::path::Path::new(&font), face.as_deref(), &text) {
+                    fail(e);
+                }
+                return Ok(());
+            }
+            Some("list") => {
+                let format = match (pargs.contains("--json"), pargs.contains("--csv")) {
+                    (true, true) => fail(FontError::Usage(
+                        "--json and --csv can not be used together".to_string(),
+                    )),
+                    (true, false) => list::ListFormat::Json,
+                    (false, true) => list::ListFormat::Csv,
+                    (false, false) => list::ListFormat::Table,
+                };
+                let dir: String = pargs.free_from_str().unwrap_or_else(arg_error);
+                if let Err(e) = list::run(std::path::Path::new(&dir), format) {
+                    fail(e);
+                }
+                return Ok(());
+            }
+            Some(command) => fail(FontError::Usage(format!("unknown command {command}"))),
+            None => {}
+        }
+
+        if let Some(config) = pargs
+            .opt_value_from_str::<_, String>("--config")
+            .unwrap_or_else(arg_error)
+        {
+            let code = config::run(std::path::Path::new(&config), pargs.contains("--force"));
+            if code != 0 {
+                exit(code);
+            }
+            return Ok(());
+        }
+
+        let inputs: Vec<String> = pargs.values_from_str("--input").unwrap_or_else(arg_error);
+        let output: String = pargs.value_from_str("--output").unwrap_or_else(arg_error);
+        let inputs = match batch::expand_inputs(&inputs) {
+            Ok(inputs) if !inputs.is_empty() => inputs,
+            Ok(_) => fail(FontError::Usage("require --input".to_string())),
+            Err(e) => fail(FontError::Usage(e)),
+        };
 
-        let file = pargs
-            .opt_value_from_str("--file")
-            .expect("--file err")
-            .and_then(|v: String| std::fs::read_to_string(v).ok())
-            .or(pargs.opt_value_from_str("--text").expect("--text err"));
+        let options = font_info::SubsetOptions {
+            format: pargs
+                .opt_value_from_str("--format")
+                .unwrap_or_else(arg_error)
+                .unwrap_or_default(),
+            profile: pargs
+                .opt_value_from_str("--profile")
+                .unwrap_or_else(arg_error)
+                .unwrap_or_default(),
+            keep_tables: pargs
+                .opt_value_from_fn("--keep-tables", font_info::parse_tables)
+                .unwrap_or_else(arg_error)
+                .unwrap_or_default(),
+            drop_tables: pargs
+                .opt_value_from_fn("--drop-tables", font_info::parse_tables)
+                .unwrap_or_else(arg_error)
+                .unwrap_or_default(),
+            family: pargs
+                .opt_value_from_str("--family")
+                .unwrap_or_else(arg_error),
+            seed: match pargs.opt_value_from_str("--seed").unwrap_or_else(arg_error) {
+                Some(seed) => font_info::NameSeed::Fixed(seed),
+                None if pargs.contains("--deterministic") => font_info::NameSeed::Hash,
+                None => font_info::NameSeed::Time,
+            },
+            features: pargs
+                .opt_value_from_fn("--features", font_info::parse_tables)
+                .unwrap_or_else(arg_error)
+                .unwrap_or_default(),
+        };
+        if let Err(e) = options.validate() {
+            fail(FontError::Usage(e));
+        }
+
+        let (file, range_text) = read_sources(&mut pargs);
+        let chunks: Option<usize> = pargs
+            .opt_value_from_str("--chunks")
+            .unwrap_or_else(arg_error);
+        let chunk_order = pargs
+            .opt_value_from_str::<_, String>("--chunk-order")
+            .unwrap_or_else(arg_error)
+            .map(|path: String| read_text(&path))
+            .unwrap_or_default();
+        let css: Option<String> = pargs.opt_value_from_str("--css").unwrap_or_else(arg_error);
+        // 只指定了范围或字符集时文本为空，切分时不指定文本表示整个字体
+        let file = file.or_else(|| (!range_text.is_empty() || chunks.is_some()).then(String::new));
+        let face: Option<String> = pargs.opt_value_from_str("--face").unwrap_or_else(arg_error);
+        let all_faces = pargs.contains("--all-faces");
+        let collection = pargs.contains("--collection");
+        let strict = pargs.contains("--strict");
+        let use_fallback = pargs.contains("--fallback");
+        let jobs: Option<usize> = pargs.opt_value_from_str("--jobs").unwrap_or_else(arg_error);
+        let report: Option<report::ReportFormat> = pargs
+            .opt_value_from_str("--report")
+            .unwrap_or_else(arg_error);
+        if report.is_some() && (chunks.is_some() || collection) {
+            fail(FontError::Usage(
+                "--report does not support --chunks and --collection".to_string(),
+            ));
+        }
+
+        if use_fallback {
+            if all_faces || collection || chunks.is_some() {
+                fail(FontError::Usage(
+                    "--fallback does not support --all-faces, --collection and --chunks"
+                        .to_string(),
+                ));
+            }
+            let Some(text) = file else {
+                fail(FontError::Usage(REQUIRE_TEXT.to_string()));
+            };
+            let subset = format!("{text}{range_text}");
+            let (coverages, missing) = fallback::Fallback {
+                output: &output,
+                face: face.as_deref(),
+                text: &text,
+                subset: &subset,
+                options: &options,
+            }
+            .run(&inputs)
+            .unwrap_or_else(|e| fail(e));
+            print_missing(&missing);
+            // 以第一个失败的字体决定退出码
+            let code = coverages
+                .iter()
+                .filter_map(|c| c.summary.as_ref())
+                .find_map(|s| s.result.as_ref().err())
+                .map(|e| e.exit_code());
+            match report {
+                Some(report::ReportFormat::Json) => {
+                    let summaries = coverages
+                        .into_iter()
+                        .filter_map(|c| c.summary)
+                        .collect::<Vec<_>>();
+                    println!("{}", report::json(&summaries));
+                }
+                None => fallback::print_coverage(&inputs, &coverages, &missing),
+            }
+            if let Some(code) = code {
+                exit(code);
+            }
+            if strict && !missing.is_empty() {
+                fail(FontError::Missing(missing.len()));
+            }
+            return Ok(());
+        }
+
+        // 多个输入或者输出使用模板时批量处理
+        if inputs.len() > 1 || output.contains('{') {
+            if all_faces || collection || chunks.is_some() {
+                fail(FontError::Usage(
+                    "--all-faces, --collection and --chunks only support a single --input"
+                        .to_string(),
+                ));
+            }
+            let Some(text) = file else {
+                fail(FontError::Usage(REQUIRE_TEXT.to_string()));
+            };
+            let subset = format!("{text}{range_text}");
+            let summaries = batch::Batch {
+                output: &output,
+                face: face.as_deref(),
+                text: &text,
+                subset: &subset,
+                strict,
+                options: &options,
+                jobs: jobs.unwrap_or_else(|| {
+                    thread::available_parallelism()
+                        .map(|n| n.get())
+                        .unwrap_or(1)
+                }),
+            }
+            .run(&inputs);
+            for s in summaries.iter().filter(|s| !s.missing.is_empty()) {
+                eprintln!("{}:", s.input.display());
+                print_missing(&s.missing);
+            }
+            match report {
+                Some(report::ReportFormat::Json) => println!("{}", report::json(&summaries)),
+                None => batch::print_summary(&summaries),
+            }
+            // 以第一个失败的字体决定退出码
+            if let Some(Err(e)) = summaries.iter().map(|s| &s.result).find(|r| r.is_err()) {
+                exit(e.exit_code());
+            }
+            return Ok(());
+        }
+        let font = std::fs::read(&inputs[0]).unwrap_or_else(|e| fail(FontError::io(&inputs[0], e)));
         match file {
             Some(text) => {
-                let font_file = allsorts::binary::read::ReadScope::new(&font)
-                    .read::<allsorts::font_data::FontData>()
-                    .expect("load font fail");
-                let provider = font_file.table_provider(0).unwrap();
-                if let Some(n) = font_info::subset_text(
-                    &provider,
-                    text.as_str(),
-                    &std::path::Path::new(&output).to_path_buf(),
-                ) {
-                    println!("{}", n);
+                // 范围和字符集内字体没有的字符是预期的，不计入缺失字符
+                let subset = format!("{text}{range_text}");
+                let font_file = font_info::load(&font).unwrap_or_else(|e| fail(e));
+                let faces = if all_faces {
+                    (0..font_info::face_count(&font)).collect::<Vec<_>>()
                 } else {
-                    eprintln!("subset fail");
-                    exit(101);
+                    match face.as_deref().map(|f| font_info::find_face(&font, f)) {
+                        Some(Some(index)) => vec![index],
+                        Some(None) => fail(FontError::Usage(format!(
+                            "face not found: {}",
+                            face.unwrap_or_default()
+                        ))),
+                        None => vec![0],
+                    }
+                };
+                let output = std::path::Path::new(&output).to_path_buf();
+
+                // 先检查缺失的字符，严格模式下不输出任何文件
+                let mut missing_of_faces = Vec::with_capacity(faces.len());
+                for index in faces.iter() {
+                    let provider = font_file
+                        .table_provider(*index)
+                        .unwrap_or_else(|e| fail(e.into()));
+                    let missing = font_info::missing_chars(&provider, &text)
+                        .unwrap_or_else(|e| fail(e.into()));
+                    print_missing(&missing);
+                    if strict && !missing.is_empty() {
+                        fail(FontError::Missing(missing.len()));
+                    }
+                    missing_of_faces.push(missing);
+                }
+
+                if let Some(chunks) = chunks {
+                    for index in faces.iter() {
+                        let provider = font_file
+                            .table_provider(*index)
+                            .unwrap_or_else(|e| fail(e.into()));
+                        let output = if faces.len() > 1 {
+                            font_info::face_output_path(&output, *index)
+                        } else {
+                            output.clone()
+                        };
+                        let css_path = match css.as_deref() {
+                            Some(css) if faces.len() > 1 => {
+                                font_info::face_output_path(std::path::Path::new(css), *index)
+                            }
+                            Some(css) => std::path::PathBuf::from(css),
+                            None => output.with_extension("css"),
+                        };
+                        match font_info::subset_chunks(
+                            &provider,
+                            subset.as_str(),
+                            chunks,
+                            &chunk_order,
+                            &output,
+                            &css_path,
+                            &options,
+                        ) {
+                            Ok(paths) => {
+                                paths.iter().for_each(|p| println!("{}", p));
+                                println!("{}", css_path.display());
+                            }
+                            Err(e) => fail(e),
+                        }
+                    }
+                } else if collection && faces.len() > 1 {
+                    // 重新打包为一个字体集合
+                    match font_info::subset_collection(
+                        &font_file,
+                        &faces,
+                        subset.as_str(),
+                        &output,
+                        &options,
+                    ) {
+                        Ok(names) => names.iter().for_each(|n| println!("{}", n)),
+                        Err(e) => fail(e),
+                    }
+                } else {
+                    let mut summaries = Vec::with_capacity(faces.len());
+                    for (index, missing) in faces.iter().zip(missing_of_faces) {
+                        let provider = font_file
+                            .table_provider(*index)
+                            .unwrap_or_else(|e| fail(e.into()));
+                        let output = if faces.len() > 1 {
+                            font_info::face_output_path(&output, *index)
+                        } else {
+                            output.clone()
+                        };
+                        let r =
+                            font_info::subset_text(&provider, subset.as_str(), &output, &options)
+                                .unwrap_or_else(|e| fail(e));
+                        if report.is_none() {
+                            println!("{}", r.family);
+                        }
+                        summaries.push(batch::Summary {
+                            input: inputs[0].clone(),
+                            face: *index,
+                            input_size: font.len() as u64,
+                            output: Some(output),
+                            missing,
+                            result: Ok(r),
+                        });
+                    }
+                    if let Some(report::ReportFormat::Json) = report {
+                        println!("{}", report::json(&summaries));
+                    }
                 }
             }
-            None => {
-                eprintln!("require --text or --file")
-            }
+            None => fail(FontError::Usage(REQUIRE_TEXT.to_string())),
         }
 
         return Ok(());
@@ -339,12 +680,108 @@ OPTIONS:
         }),
     )
 }
+
+/// 没有提供文本时的提示
+const REQUIRE_TEXT: &str =
+    "require --text, --file, --corpus, --unicodes, --unicodes-file or --charset";
+
+/// 输出错误并以对应的退出码退出
+fn fail(e: FontError) -> ! {
+    eprintln!("{e}");
+    exit(e.exit_code());
+}
+
+/// 命令行参数不合法
+fn arg_error<T>(e: pico_args::Error) -> T {
+    fail(FontError::Usage(e.to_string()))
+}
+
+/// 读取文本文件，失败时退出
+fn read_text(path: &str) -> String {
+    std::fs::read_to_string(path)
+        .unwrap_or_else(|e| fail(FontError::io(std::path::Path::new(path), e)))
+}
+
+/// 读取 `--text`、`--file`、`--corpus` 的文本和 `--unicodes`、`--unicodes-file`、`--charset` 的字符
+///
+/// 没有指定文本时第一项为 None
+fn read_sources(pargs: &mut pico_args::Arguments) -> (Option<String>, String) {
+    let file = pargs
+        .opt_value_from_str("--file")
+        .unwrap_or_else(arg_error)
+        .map(|v: String| read_text(&v))
+        .or(pargs.opt_value_from_str("--text").unwrap_or_else(arg_error));
+    let include: Vec<String> = pargs.values_from_str("--include").unwrap_or_else(arg_error);
+    let exclude: Vec<String> = pargs.values_from_str("--exclude").unwrap_or_else(arg_error);
+    let file = match pargs
+        .opt_value_from_str::<_, String>("--corpus")
+        .unwrap_or_else(arg_error)
+    {
+        Some(dir) => match corpus::collect(std::path::Path::new(&dir), &include, &exclude) {
+            Ok(corpus) => Some(file.unwrap_or_default() + &corpus),
+            Err(e) => fail(FontError::io(std::path::Path::new(&dir), e)),
+        },
+        None => file,
+    };
+    let mut unicodes = Vec::new();
+    if let Some(u) = pargs
+        .opt_value_from_str::<_, String>("--unicodes")
+        .unwrap_or_else(arg_error)
+    {
+        unicodes.push(u);
+    }
+    if let Some(path) = pargs
+        .opt_value_from_str::<_, String>("--unicodes-file")
+        .unwrap_or_else(arg_error)
+    {
+        unicodes.push(read_text(&path));
+    }
+    let charsets: Vec<String> = pargs.values_from_str("--charset").unwrap_or_else(arg_error);
+    let range_text = match font_info::parse_unicodes(&unicodes.join("\n"))
+        .and_then(|text| Ok(text + &charset::chars_of(&charsets.join(","))?))
+    {
+        Ok(v) => v,
+        Err(e) => fail(FontError::Usage(e)),
+    };
+    (file, range_text)
+}
+
+/// 输出字体中缺少的字符
+fn print_missing(missing: &[char]) {
+    if missing.is_empty() {
+        return;
+    }
+    eprintln!(
+        "missing {} characters: {}",
+        missing.len(),
+        missing.iter().collect::<String>()
+    );
+    eprintln!(
+        "code points: {}",
+        missing
+            .iter()
+            .map(|c| format!("U+{:04X}", *c as u32))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+}
+
 #[derive(Clone)]
 struct FontInner {
     path: String,
+    /// 字体集合中的下标
+    index: usize,
+    /// 所在文件包含的字体数量
+    face_count: usize,
     mock_name: String,
     font_name: String,
     file_name: String,
+    /// 文件格式，无法解析时为 None
+    container: Option<Container>,
+    /// 文件大小
+    file_size: u64,
+    /// 字形数量、度量、表和 name 表的所有记录
+    info: FaceInfo,
 }
 
 enum Msg {
@@ -360,6 +797,12 @@ struct FontViewApp {
     example: String,
     subset: SubsetModal,
     subset_open: bool,
+    /// 正在查看名称记录的字体
+    names: Option<FontInner>,
+    /// 选中的字体，在侧边栏显示详细信息
+    selected: Option<FontInner>,
+    /// 显示名称时偏好的语言
+    language: DisplayLanguage,
     sx: Sender<Msg>,
     rx: Receiver<Msg>,
 }
@@ -374,6 +817,9 @@ impl FontViewApp {
             example: "测试文本".to_string(),
             subset: SubsetModal::default(),
             subset_open: false,
+            names: None,
+            selected: None,
+            language: DisplayLanguage::default(),
             sx,
             rx,
         };
@@ -480,7 +926,6 @@ fn setup_fonts(ctx: &egui::Context, dir: &PathBuf) -> Vec<FontInner> {
         //         std::fs::read(font_path).expect("read fail"),
         //     )), // 替换为实际字体数据
         // );
-        let fmn = font_name.to_string();
         // fonts.families.insert(
         //     FontFamily::Name(fmn.clone().into()),
         //     vec![font_name.to_string()],
@@ -488,41 +933,68 @@ fn setup_fonts(ctx: &egui::Context, dir: &PathBuf) -> Vec<FontInner> {
 
         let cow: std::borrow::Cow<'_, [u8]> =
             std::borrow::Cow::Owned(std::fs::read(font_path).expect("read fail"));
-        let font_name_real = dump(&cow.clone());
-        if font_name_real.is_empty() {
-            continue;
-        }
-        ctx.add_font(FontInsert::new(
-            font_name,
-            FontData {
-                font: cow.clone(),
-                index: 0,
-                tweak: Default::default(),
-            },
-            // egui::FontData::from_owned(std::fs::read(font_path).expect("read fail")),
-            vec![egui::epaint::text::InsertFontFamily {
-                family: egui::FontFamily::Name(fmn.clone().into()),
-                priority: egui::epaint::text::FontPriority::Lowest,
-            }],
-        ));
-
-        fm.push(FontInner {
-            font_name: font_name_real,
-            path: font_path.clone(),
-            mock_name: font_name.into(),
-            file_name: format!(
-                "{:?}",
-                std::path::Path::new(font_path)
-                    .file_name()
-                    .unwrap_or_default()
-            ),
-        });
+        // 字体集合中的每个字体单独展示
+        let face_count = font_info::face_count(&cow);
+        let file_info = inspect::inspect(&cow).ok();
+        for index in 0..face_count {
+            let font_name_real = dump(&cow, index);
+            if font_name_real.is_empty() {
+                continue;
+            }
+            let font_name = if index == 0 {
+                font_name.to_string()
+            } else {
+                format!("{font_name}_{index}")
+            };
+            ctx.add_font(FontInsert::new(
+                &font_name,
+                FontData {
+                    font: cow.clone(),
+                    index: index as u32,
+                    tweak: Default::default(),
+                },
+                // egui::FontData::from_owned(std::fs::read(font_path).expect("read fail")),
+                vec![egui::epaint::text::InsertFontFamily {
+                    family: egui::FontFamily::Name(font_name.clone().into()),
+                    priority: egui::epaint::text::FontPriority::Lowest,
+                }],
+            ));
+
+            let info = file_info
+                .as_ref()
+                .and_then(|f| f.faces.get(index).cloned())
+                .unwrap_or_default();
+            fm.push(FontInner {
+                font_name: font_name_real,
+                path: font_path.clone(),
+                index,
+                face_count,
+                mock_name: font_name,
+                file_name: format!(
+                    "{:?}",
+                    std::path::Path::new(font_path)
+                        .file_name()
+                        .unwrap_or_default()
+                ),
+                container: file_info.as_ref().map(|f| f.container),
+                file_size: cow.len() as u64,
+                info,
+            });
+        }
     }
 
     fm
 }
 impl eframe::App for FontViewApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // 侧边栏需要在中间的面板之前添加
+        if let Some(font) = &self.selected {
+            let mut open = true;
+            show_details(ctx, font, self.language, &mut open);
+            if !open {
+                self.selected = None;
+            }
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             // ui.text_edit_singleline(&mut self.example).w;
             ui.add(TextEdit::singleline(&mut self.example).desired_width(f32::INFINITY));
@@ -567,6 +1039,16 @@ impl eframe::App for FontViewApp {
                 }
             });
 
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("名称语言")
+                    .selected_text(self.language.to_string())
+                    .show_ui(ui, |ui| {
+                        for language in DisplayLanguage::ALL {
+                            ui.selectable_value(&mut self.language, language, language.to_string());
+                        }
+                    });
+            });
+
             if let Ok(r) = self.rx.try_recv() {
                 match r {
                     Msg::Dir(dir) => {
@@ -576,6 +1058,8 @@ impl eframe::App for FontViewApp {
                     }
                     Msg::Font(font) => {
                         self.font = font;
+                        self.names = None;
+                        self.selected = None;
                         self.loading = false;
                         // ctx.request_repaint();
                     }
@@ -602,12 +1086,30 @@ impl eframe::App for FontViewApp {
                                 |ui| {
                                     if !self.loading {
                                         for ele in self.font.iter() {
-                                            view_panel(ui, &self.example, ele, |_ui, font| {
-                                                // self.subset.show(ui.ctx(), font);
-                                                self.subset_open = true;
-                                                self.subset.font = Some(font.clone());
-                                                self.subset.text = self.example.clone();
-                                            });
+                                            let actions = PanelActions {
+                                                selected: self.selected.as_ref().is_some_and(|f| {
+                                                    f.path == ele.path && f.index == ele.index
+                                                }),
+                                                sub: |_ui: &mut Ui, font: &FontInner| {
+                                                    // self.subset.show(ui.ctx(), font);
+                                                    self.subset_open = true;
+                                                    self.subset.font = Some(font.clone());
+                                                    self.subset.text = self.example.clone();
+                                                },
+                                                names: |font: &FontInner| {
+                                                    self.names = Some(font.clone())
+                                                },
+                                                select: |font: &FontInner| {
+                                                    self.selected = Some(font.clone())
+                                                },
+                                            };
+                                            view_panel(
+                                                ui,
+                                                &self.example,
+                                                ele,
+                                                self.language,
+                                                actions,
+                                            );
                                         }
                                     }
                                 },
@@ -619,47 +1121,290 @@ impl eframe::App for FontViewApp {
         if self.subset_open {
             self.subset.show(ctx, &mut self.subset_open);
         }
+        if let Some(font) = &self.names {
+            let mut open = true;
+            show_names(ctx, font, &mut open);
+            if !open {
+                self.names = None;
+            }
+        }
     }
 }
 
-fn view_panel(
+/// 字体卡片的选中状态和点击后的操作
+struct PanelActions<S, N, P> {
+    /// 是否是侧边栏中显示的字体
+    selected: bool,
+    /// 点击子集化
+    sub: S,
+    /// 点击名称
+    names: N,
+    /// 点击预览文本
+    select: P,
+}
+
+fn view_panel<S, N, P>(
     ui: &mut Ui,
     example: &str,
     fname: &FontInner,
-    sub: impl FnOnce(&mut Ui, &FontInner),
-) {
+    language: DisplayLanguage,
+    actions: PanelActions<S, N, P>,
+) where
+    S: FnOnce(&mut Ui, &FontInner),
+    N: FnOnce(&FontInner),
+    P: FnOnce(&FontInner),
+{
+    let PanelActions {
+        selected,
+        sub,
+        names,
+        select,
+    } = actions;
     egui::Frame::default()
-        .stroke(ui.visuals().widgets.noninteractive.bg_stroke)
+        .stroke(if selected {
+            ui.visuals().selection.stroke
+        } else {
+            ui.visuals().widgets.noninteractive.bg_stroke
+        })
         .corner_radius(ui.visuals().widgets.noninteractive.corner_radius)
         .show(ui, |ui| {
-            ui.label(RichText::new(example).font(FontId::new(
-                25.0,
-                FontFamily::Name(fname.mock_name.to_string().into()),
-            )));
+            // 点击预览文本选中字体
+            if ui
+                .add(
+                    egui::Label::new(RichText::new(example).font(FontId::new(
+                        25.0,
+                        FontFamily::Name(fname.mock_name.to_string().into()),
+                    )))
+                    .sense(egui::Sense::click()),
+                )
+                .on_hover_cursor(egui::CursorIcon::PointingHand)
+                .clicked()
+            {
+                select(fname);
+            }
             ui.separator();
             ui.horizontal(|ui| {
                 if ui.button("子集化").clicked() {
                     sub(ui, fname);
                 }
+                if ui.button("名称").clicked() {
+                    names(fname);
+                }
+                // 按偏好的语言选择名称，族名称优先使用 16、17 号记录
+                let full_name = inspect::localized_name(&fname.info.names, &[4], language)
+                    .unwrap_or(fname.font_name.as_str());
+                let family = inspect::localized_name(&fname.info.names, &[16, 1], language);
+                let subfamily = inspect::localized_name(&fname.info.names, &[17, 2], language);
                 if ui
-                    .label(format!("[{}]", fname.font_name))
+                    .label(format!("[{full_name}]"))
                     .on_hover_cursor(egui::CursorIcon::Copy)
                     .clicked()
                 {
-                    ui.ctx().copy_text(fname.font_name.clone());
+                    ui.ctx().copy_text(full_name.to_string());
                 };
-                ui.label(fname.file_name.as_str());
+                if let Some(family) = family
+                    && ui
+                        .weak(format!("{family} / {}", subfamily.unwrap_or_default()))
+                        .on_hover_cursor(egui::CursorIcon::Copy)
+                        .clicked()
+                {
+                    ui.ctx().copy_text(family.to_string());
+                }
+                if fname.face_count > 1 {
+                    ui.label(format!("{} #{}", fname.file_name, fname.index));
+                } else {
+                    ui.label(fname.file_name.as_str());
+                }
             });
         });
     ui.add_space(15.);
 }
 
+/// 侧边栏显示选中字体的详细信息，点击值复制
+fn show_details(ctx: &egui::Context, font: &FontInner, language: DisplayLanguage, open: &mut bool) {
+    let face = &font.info;
+    let name = |ids: &[u16]| inspect::localized_name(&face.names, ids, language).map(String::from);
+    let mut rows = vec![
+        (
+            "格式",
+            font.container.map(|c| c.to_string()).unwrap_or_default(),
+        ),
+        ("字体数量", font.face_count.to_string()),
+        ("字形数量", face.glyphs.to_string()),
+        ("unitsPerEm", face.units_per_em.to_string()),
+        (
+            "版本",
+            name(&[5]).unwrap_or_else(|| format!("{:.3}", face.revision)),
+        ),
+        (
+            "厂商",
+            face.os2
+                .as_ref()
+                .map(|o| o.vendor.clone())
+                .unwrap_or_default(),
+        ),
+        ("设计师", name(&[9]).unwrap_or_default()),
+    ];
+    if let Some(os2) = &face.os2 {
+        rows.push(("字重", os2.weight.to_string()));
+        rows.push(("字宽", os2.width.to_string()));
+    }
+    if let Some(angle) = face.italic_angle {
+        rows.push(("斜体角度", angle.to_string()));
+    }
+    if let Some(hhea) = &face.horizontal {
+        rows.push(("hhea", info::metrics_display(hhea)));
+    }
+    if let Some(os2) = &face.os2 {
+        if let Some(typo) = &os2.typo {
+            rows.push(("typo", info::metrics_display(typo)));
+        }
+        if let Some((ascent, descent)) = os2.win {
+            rows.push(("win", format!("ascent {ascent}, descent {descent}")));
+        }
+    }
+    if let Some(vhea) = &face.vertical {
+        rows.push(("vhea", info::metrics_display(vhea)));
+    }
+    if let Some(os2) = &face.os2 {
+        rows.push(("嵌入许可", info::fs_type_display(os2.fs_type)));
+    }
+    rows.push(("文件大小", font.file_size.to_string()));
+
+    let copyable = |ui: &mut Ui, value: &str| {
+        if ui
+            .label(value)
+            .on_hover_cursor(egui::CursorIcon::Copy)
+            .clicked()
+        {
+            ui.ctx().copy_text(value.to_string());
+        }
+    };
+    egui::SidePanel::right("details")
+        .resizable(true)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("详情");
+                if ui.button("关闭").clicked() {
+                    *open = false;
+                }
+            });
+            ui.separator();
+            ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("details")
+                    .striped(true)
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (label, value) in rows.iter() {
+                            ui.label(*label);
+                            copyable(ui, value);
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                ui.strong("表");
+                egui::Grid::new("details_tables")
+                    .striped(true)
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (tag, len) in face.tables.iter() {
+                            copyable(ui, &font_info::tag_display(*tag));
+                            copyable(ui, &len.to_string());
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+}
+
+/// 显示 name 表的所有记录，点击值复制
+fn show_names(ctx: &egui::Context, font: &FontInner, open: &mut bool) {
+    Window::new(format!("name [{}]", font.font_name))
+        .id(Id::new("names"))
+        .open(open)
+        .resizable(true)
+        .show(ctx, |ui| {
+            if font.info.names.is_empty() {
+                ui.label("没有 name 表");
+                return;
+            }
+            ScrollArea::both().show(ui, |ui| {
+                egui::Grid::new("name_records")
+                    .striped(true)
+                    .num_columns(5)
+                    .show(ui, |ui| {
+                        for header in ["ID", "名称", "编码", "语言", "值"] {
+                            ui.strong(header);
+                        }
+                        ui.end_row();
+                        for r in font.info.names.iter() {
+                            ui.label(r.name_id.to_string());
+                            ui.label(r.name_id_display());
+                            ui.label(format!(
+                                "{}/{} {}",
+                                r.platform,
+                                r.encoding,
+                                r.encoding_display()
+                            ));
+                            ui.label(
+                                r.language_tag
+                                    .clone()
+                                    .unwrap_or_else(|| format!("0x{:04X}", r.language)),
+                            );
+                            match &r.value {
+                                Some(value) => {
+                                    if ui
+                                        .label(value)
+                                        .on_hover_cursor(egui::CursorIcon::Copy)
+                                        .clicked()
+                                    {
+                                        ui.ctx().copy_text(value.clone());
+                                    }
+                                }
+                                None => {
+                                    ui.weak("(无法解码)");
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+}
+
 #[derive(Default)]
 struct SubsetModal {
     text: String,
     font: Option<FontInner>,
+    format: font_info::OutputFormat,
+    /// 子集化字体集合中的所有字体
+    all_faces: bool,
+    /// 所有字体打包为一个字体集合
+    collection: bool,
+    profile: font_info::Profile,
+    keep_tables: String,
+    drop_tables: String,
+    /// GSUB 闭包使用的特性，为空时使用所有特性
+    features: String,
+    /// 子集字体名称，为空时随机生成
+    family: String,
+    /// 随机名称由字体和文本决定
+    deterministic: bool,
+    /// 随机名称的种子，为空时使用当前时间
+    seed: String,
+    /// unicode 范围，和文本合并后子集化
+    unicodes: String,
+    /// 选中的内置字符集
+    charsets: std::collections::BTreeSet<&'static str>,
+    /// 缓存的字体文件，避免每次修改文本都重新读取
+    buffer: Option<(String, Vec<u8>)>,
+    /// 上次检查缺失字符时的字体路径、下标和文本
+    missing_key: (String, usize, String),
+    /// 字体中缺少的字符
+    missing: Vec<char>,
     notify_modal: bool,
-    result: Option<String>,
+    /// 子集化结果，成功时为新字体名称
+    result: Option<Result<String, FontError>>,
 }
 
 impl SubsetModal {
@@ -670,16 +1415,22 @@ impl SubsetModal {
             ..
         } = self;
         if *notify_modal {
-            let modal = Modal::new(Id::new(if result.is_some() { "Success" } else { "fail" }))
-                .show(ui.ctx(), |ui| {
+            let success = matches!(result, Some(Ok(_)));
+            let modal = Modal::new(Id::new(if success { "Success" } else { "fail" })).show(
+                ui.ctx(),
+                |ui| {
                     ui.set_width(200.0);
-                    ui.heading(if result.is_some() { "Success" } else { "fail" });
+                    ui.heading(if success { "Success" } else { "fail" });
+                    if let Some(Err(e)) = result {
+                        ui.colored_label(ui.visuals().error_fg_color, e.to_string());
+                    }
 
                     ui.add_space(32.0);
                     if ui.button("Ok").clicked() {
                         ui.close();
                     }
-                });
+                },
+            );
 
             if modal.should_close() {
                 *notify_modal = false;
@@ -687,330 +1438,249 @@ impl SubsetModal {
         }
     }
 
+    /// 文本或字体变化后重新检查缺失的字符
+    fn update_missing(&mut self) {
+        let Some(f) = &self.font else {
+            return;
+        };
+        let key = (f.path.clone(), f.index, self.text.clone());
+        if self.missing_key == key {
+            return;
+        }
+        if self.buffer.as_ref().is_none_or(|(p, _)| *p != f.path) {
+            self.buffer = std::fs::read(&f.path).ok().map(|b| (f.path.clone(), b));
+        }
+        self.missing = self
+            .buffer
+            .as_ref()
+            .and_then(|(_, buffer)| {
+                let font_file = font_info::load(buffer).ok()?;
+                let provider = font_file.table_provider(f.index).ok()?;
+                font_info::missing_chars(&provider, &self.text).ok()
+            })
+            .unwrap_or_default();
+        self.missing_key = key;
+    }
+
     fn ui(&mut self, ui: &mut Ui) {
         ui.text_edit_multiline(&mut self.text);
 
-        if ui.button("确认").clicked() {
-            // 子集化
-            if let Some(f) = &self.font
-                && let Some(out) = rfd::FileDialog::new()
-                    .set_file_name(f.file_name.as_str().replace("\"", ""))
-                    .save_file()
-                && !self.text.is_empty()
+        self.update_missing();
+        if !self.missing.is_empty() {
+            let missing = self.missing.iter().collect::<String>();
+            if ui
+                .colored_label(
+                    ui.visuals().warn_fg_color,
+                    format!("缺少 {} 个字符: {}", self.missing.len(), missing),
+                )
+                .on_hover_text(
+                    self.missing
+                        .iter()
+                        .map(|c| format!("U+{:04X}", *c as u32))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                )
+                .on_hover_cursor(egui::CursorIcon::Copy)
+                .clicked()
             {
-                let buffer = std::fs::read(f.path.as_str()).unwrap();
-                let font_file = allsorts::binary::read::ReadScope::new(&buffer)
-                    .read::<allsorts::font_data::FontData>()
-                    .unwrap();
-                let provider = font_file.table_provider(0).unwrap();
-
-                self.notify_modal = true;
-                self.result = font_info::subset_text(&provider, &self.text, &out);
-                // ui.close();
+                ui.ctx().copy_text(missing);
             }
         }
 
-        self.notify_modal(ui);
-    }
-
-    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
-        let w = Window::new("subset").open(open).resizable(true);
-        w.show(ctx, |ui| {
-            self.ui(ui);
+        ui.horizontal(|ui| {
+            ui.label("Unicode 范围");
+            ui.add(TextEdit::singleline(&mut self.unicodes).hint_text("U+0000-00FF,U+4E00-9FFF"));
         });
-    }
-}
-
-mod font_info {
-    use std::convert::TryFrom;
-    use std::io::Write;
-    use std::path::PathBuf;
-    use std::str;
-
-    use allsorts::gsub::{GlyphOrigin, RawGlyph, RawGlyphFlags};
-    use allsorts::subset::SubsetProfile;
-
-    use allsorts::binary::read::{ReadScope, ReadScopeOwned};
-    use allsorts::error::ParseError;
-    use allsorts::font_data::FontData;
-    use allsorts::tables::{FontTableProvider, NameTable, OffsetTable, OpenTypeData, TTCHeader};
-    use allsorts::tag::{self};
-    use allsorts::woff::WoffFont;
-    use allsorts::woff2::Woff2Font;
+        let range_text = font_info::parse_unicodes(&self.unicodes);
+        if let Err(e) = &range_text {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+        egui::CollapsingHeader::new(format!("内置字符集 ({})", self.charsets.len())).show(
+            ui,
+            |ui| {
+                for c in charset::CHARSETS {
+                    let mut checked = self.charsets.contains(c.name);
+                    if ui.checkbox(&mut checked, c.description).changed() {
+                        if checked {
+                            self.charsets.insert(c.name);
+                        } else {
+                            self.charsets.remove(c.name);
+                        }
+                    }
+                }
+            },
+        );
 
-    pub type BoxError = Box<dyn std::error::Error>;
-    ///
-    /// 字体子集化
-    ///
-    pub(crate) fn subset_text<F: FontTableProvider>(
-        font_provider: &F,
-        text: &str,
-        output_path: &PathBuf,
-    ) -> Option<String> {
-        let text = format!("{text}?◻"); // 添加两个占位符，用于字符不存在时渲染，避免完全不渲染的空白
+        egui::ComboBox::from_label("输出格式")
+            .selected_text(self.format.to_string())
+            .show_ui(ui, |ui| {
+                for format in [
+                    font_info::OutputFormat::Auto,
+                    font_info::OutputFormat::Sfnt,
+                    font_info::OutputFormat::Woff,
+                    font_info::OutputFormat::Woff2,
+                ] {
+                    ui.selectable_value(&mut self.format, format, format.to_string());
+                }
+            });
 
-        match do_subset_text(
-            font_provider,
-            remove_duplicate_chars(&text).as_str(),
-            output_path,
-        ) {
-            Ok(v) => String::from_utf8(v).ok(),
-            Err(e) => {
-                s_error!("subset fail {:?}", e);
-                None
+        egui::ComboBox::from_label("保留的表")
+            .selected_text(self.profile.to_string())
+            .show_ui(ui, |ui| {
+                for profile in [font_info::Profile::Minimal, font_info::Profile::Web] {
+                    ui.selectable_value(&mut self.profile, profile, profile.to_string());
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("额外保留");
+            ui.add(TextEdit::singleline(&mut self.keep_tables).hint_text("GPOS,kern"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("删除");
+            ui.add(TextEdit::singleline(&mut self.drop_tables).hint_text("gasp"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("GSUB 特性");
+            ui.add(TextEdit::singleline(&mut self.features).hint_text("全部，例如 liga,vert,locl"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("字体名称");
+            ui.add(TextEdit::singleline(&mut self.family).hint_text("随机生成"));
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.deterministic, "可复现");
+            if !self.deterministic {
+                ui.label("种子");
+                ui.add(TextEdit::singleline(&mut self.seed).hint_text("当前时间"));
             }
+        });
+        let options = self.options();
+        if let Err(e) = &options {
+            ui.colored_label(ui.visuals().error_fg_color, e);
         }
-    }
-    /// 文本去重
-    fn remove_duplicate_chars(input: &str) -> String {
-        let mut seen = std::collections::HashSet::new();
-        let mut result = String::new();
 
-        for c in input.chars() {
-            if !seen.contains(&c) {
-                seen.insert(c);
-                result.push(c);
+        if let Some(f) = &self.font
+            && f.face_count > 1
+        {
+            ui.checkbox(&mut self.all_faces, format!("全部 {} 个字体", f.face_count));
+            if self.all_faces {
+                ui.checkbox(&mut self.collection, "打包为字体集合");
             }
         }
 
-        result
-    }
-
-    /// 随机数算法
-    fn lcg(seed: u32) -> u32 {
-        let a: u64 = 1664525;
-        let c: u64 = 1013904223;
-        let m: u64 = 1 << 32;
-        ((a * seed as u64 + c) % m) as u32
-    }
-
-    fn do_subset_text<F: FontTableProvider>(
-        font_provider: &F,
-        text: &str,
-        output_path: &PathBuf,
-    ) -> Result<Vec<u8>, BoxError> {
-        // Work out the glyphs we want to keep from the text
-        let mut glyphs = chars_to_glyphs(font_provider, text).unwrap();
-        let notdef = RawGlyph {
-            unicodes: allsorts::tinyvec::tiny_vec![],
-            glyph_index: 0,
-            liga_component_pos: 0,
-            glyph_origin: GlyphOrigin::Direct,
-            flags: RawGlyphFlags::empty(),
-            variation: None,
-            extra_data: (),
-        };
-        glyphs.insert(0, Some(notdef));
-
-        let mut glyphs: Vec<RawGlyph<()>> = glyphs.into_iter().flatten().collect();
-        glyphs.sort_by(|a, b| a.glyph_index.cmp(&b.glyph_index));
-        let mut glyph_ids = glyphs
-            .iter()
-            .map(|glyph| glyph.glyph_index)
-            .collect::<Vec<_>>();
-        glyph_ids.dedup();
-        if glyph_ids.is_empty() {
-            panic!("no glyphs left in font");
-        }
-
-        s_info!("Number of glyphs in new font: {}", glyph_ids.len());
-
-        // Subset
-        let mut new_font = allsorts::subset::subset(
-            font_provider,
-            &glyph_ids,
-            &SubsetProfile::Minimal,
-            allsorts::subset::CmapTarget::Unrestricted,
-        )?;
-
-        let name = do_dump(new_font.as_slice())?;
-        let mut rep = Vec::new();
-        if let Some(name) = name.1 {
-            // 修改name
-            let v = b"QWERTYUIOPASDFGHJKLMNBVCXZ";
-
-            let mut seed = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)?
-                .as_millis() as u32;
-            let len = name.scope().data().len();
+        if ui.button("确认").clicked() {
+            // 子集化
+            if let Ok(options) = options
+                && let Ok(range_text) = range_text
+                && let Some(f) = &self.font
+                && !(self.text.is_empty() && range_text.is_empty() && self.charsets.is_empty())
+                && let Some(out) = rfd::FileDialog::new()
+                    .set_file_name(self.file_name(f))
+                    .save_file()
+            {
+                let charsets = self
+                    .charsets
+                    .iter()
+                    .filter_map(|name| charset::find(name))
+                    .map(|c| c.chars())
+                    .collect::<String>();
+                let text = format!("{}{range_text}{charsets}", self.text);
 
-            for i in 0..len {
-                seed = lcg(seed);
-                let s = seed % v.len() as u32;
-                let t = v[s as usize];
-                new_font[name.base + i] = t;
-                rep.push(t);
+                self.notify_modal = true;
+                self.result = Some(self.subset(f, &text, &out, &options));
+                // ui.close();
             }
         }
 
-        // Write out the new font
-        let mut output = std::fs::File::create(output_path)?;
-        output.write_all(&new_font)?;
-
-        Ok(rep)
+        self.notify_modal(ui);
     }
 
-    fn chars_to_glyphs<F: FontTableProvider>(
-        font_provider: &F,
+    /// 子集化选中的字体，返回新字体名称，多个字体时用逗号连接
+    ///
+    /// 字体文件在打开后被修改或删除时返回错误，而不是崩溃
+    fn subset(
+        &self,
+        f: &FontInner,
         text: &str,
-    ) -> Result<Vec<Option<RawGlyph<()>>>, BoxError> {
-        let cmap_data = font_provider.read_table_data(allsorts::tag::CMAP)?;
-        let cmap = allsorts::binary::read::ReadScope::new(&cmap_data)
-            .read::<allsorts::tables::cmap::Cmap>()?;
-        let (_, cmap_subtable) = allsorts::font::read_cmap_subtable(&cmap)?.ok_or("fail")?;
-
-        let glyphs = text
-            .chars()
-            .map(|ch| map(&cmap_subtable, ch, None))
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(glyphs)
-    }
-    fn map(
-        cmap_subtable: &allsorts::tables::cmap::CmapSubtable,
-        ch: char,
-        variation: Option<allsorts::unicode::VariationSelector>,
-    ) -> Result<Option<RawGlyph<()>>, allsorts::error::ParseError> {
-        if let Some(glyph_index) = cmap_subtable.map_glyph(ch as u32)? {
-            let glyph = make(ch, glyph_index, variation);
-            Ok(Some(glyph))
-        } else {
-            Ok(None)
-        }
-    }
-    fn make(
-        ch: char,
-        glyph_index: u16,
-        variation: Option<allsorts::unicode::VariationSelector>,
-    ) -> RawGlyph<()> {
-        RawGlyph {
-            unicodes: allsorts::tinyvec::tiny_vec![[char; 1] => ch],
-            glyph_index,
-            liga_component_pos: 0,
-            glyph_origin: GlyphOrigin::Char(ch),
-            flags: RawGlyphFlags::empty(),
-            variation,
-            extra_data: (),
-        }
-    }
-    pub(crate) fn dump(data: &[u8]) -> String {
-        match do_dump(data) {
-            Ok(v) => v.0,
-            Err(e) => {
-                s_error!("dump error {:?}", e);
-                String::new()
-            }
+        out: &std::path::PathBuf,
+        options: &font_info::SubsetOptions,
+    ) -> Result<String, FontError> {
+        let path = std::path::Path::new(&f.path);
+        let buffer = std::fs::read(path).map_err(|e| FontError::io(path, e))?;
+        let font_file = font_info::load(&buffer)?;
+
+        let all_faces = self.all_faces && f.face_count > 1;
+        if all_faces && self.collection {
+            let faces = (0..f.face_count).collect::<Vec<_>>();
+            return Ok(
+                font_info::subset_collection(&font_file, &faces, text, out, options)?.join(","),
+            );
         }
+        let faces = if all_faces {
+            (0..f.face_count).collect()
+        } else {
+            vec![f.index]
+        };
+        faces
+            .into_iter()
+            .map(|index| {
+                let provider = font_file.table_provider(index)?;
+                let out = if all_faces {
+                    font_info::face_output_path(out, index)
+                } else {
+                    out.clone()
+                };
+                Ok(font_info::subset_text(&provider, text, &out, options)?.family)
+            })
+            .collect::<Result<Vec<_>, FontError>>()
+            .map(|names| names.join(","))
     }
-    fn do_dump(data: &[u8]) -> Result<(String, Option<ReadScopeOwned>), BoxError> {
-        let scope = ReadScope::new(data);
-        let font_file = scope.read::<FontData>()?;
 
-        match &font_file {
-            FontData::OpenType(font_file) => match &font_file.data {
-                OpenTypeData::Single(ttf) => dump_ttf(&font_file.scope, ttf),
-                OpenTypeData::Collection(ttc) => dump_ttc(&font_file.scope, ttc),
+    fn options(&self) -> Result<font_info::SubsetOptions, String> {
+        let options = font_info::SubsetOptions {
+            format: self.format,
+            profile: self.profile,
+            keep_tables: font_info::parse_tables(&self.keep_tables)?,
+            drop_tables: font_info::parse_tables(&self.drop_tables)?,
+            features: font_info::parse_tables(&self.features)?,
+            family: Some(self.family.clone()),
+            seed: if self.deterministic {
+                font_info::NameSeed::Hash
+            } else if self.seed.trim().is_empty() {
+                font_info::NameSeed::Time
+            } else {
+                font_info::NameSeed::Fixed(
+                    self.seed
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("invalid seed {}", self.seed))?,
+                )
             },
-            FontData::Woff(woff_file) => dump_woff(woff_file),
-            FontData::Woff2(woff_file) => dump_woff2(woff_file, 0),
-        }
-    }
-
-    fn dump_ttc<'a>(
-        scope: &ReadScope<'a>,
-        ttc: &TTCHeader<'a>,
-    ) -> Result<(String, Option<ReadScopeOwned>), BoxError> {
-        if let Some(offset_table_offset) = (&ttc.offset_tables).into_iter().next() {
-            let offset_table_offset =
-                usize::try_from(offset_table_offset).map_err(ParseError::from)?;
-            let offset_table = scope.offset(offset_table_offset).read::<OffsetTable>()?;
-            return dump_ttf(scope, &offset_table);
-        }
-        Ok((String::new(), None))
-    }
-
-    fn dump_ttf<'a>(
-        scope: &ReadScope<'a>,
-        ttf: &OffsetTable<'a>,
-    ) -> Result<(String, Option<ReadScopeOwned>), BoxError> {
-        if let Some(name_table_data) = ttf.read_table(scope, tag::NAME)? {
-            let name_table = name_table_data.read::<NameTable>()?;
-            return dump_name_table(&name_table);
-        }
-
-        Ok((String::new(), None))
+        };
+        options.validate()?;
+        Ok(options)
     }
 
-    fn dump_woff(woff: &WoffFont<'_>) -> Result<(String, Option<ReadScopeOwned>), BoxError> {
-        if let Some(entry) = woff
-            .table_directory
-            .iter()
-            .find(|entry| entry.tag == tag::NAME)
-        {
-            let table = entry.read_table(&woff.scope)?;
-            let name_table = table.scope().read::<NameTable>()?;
-            return dump_name_table(&name_table);
-        }
-
-        Ok((String::new(), None))
-    }
-
-    fn dump_woff2<'a>(
-        woff: &Woff2Font<'a>,
-        index: usize,
-    ) -> Result<(String, Option<ReadScopeOwned>), BoxError> {
-        if let Some(table) = woff.read_table(tag::NAME, index)? {
-            s_info!();
-            let name_table = table.scope().read::<NameTable>()?;
-            return dump_name_table(&name_table);
-        }
-
-        Ok((String::new(), None))
-    }
-    fn dump_name_table(
-        name_table: &allsorts::tables::NameTable,
-    ) -> Result<(String, Option<ReadScopeOwned>), BoxError> {
-        use encoding_rs::{MACINTOSH, UTF_16BE};
-        for name_record in &name_table.name_records {
-            let platform = name_record.platform_id;
-            let encoding = name_record.encoding_id;
-            let language = name_record.language_id;
-            let offset = usize::from(name_record.offset);
-            let length = usize::from(name_record.length);
-            let name_scope = name_table.string_storage.offset_length(offset, length)?;
-            let name_data = name_scope.data();
-
-            // s_info!(
-            //     "offset={}, length = {length},{:?}",
-            //     name_table.string_storage.base + offset,
-            //     name_data
-            // );
-            let name = match (platform, encoding) {
-                (0, _) => decode(UTF_16BE, name_data),
-                (1, 0) => decode(MACINTOSH, name_data),
-                (3, 0) => decode(UTF_16BE, name_data),
-                (3, 1) => decode(UTF_16BE, name_data),
-                (3, 10) => decode(UTF_16BE, name_data),
-                _ => format!(
-                    "(unknown platform={} encoding={} language={})",
-                    platform, encoding, language
-                ),
-            };
-            if let NameTable::FULL_FONT_NAME = name_record.name_id {
-                return Ok((name, Some(ReadScopeOwned::new(name_scope))));
-            }
+    /// 保存对话框的默认文件名，指定了格式时替换后缀
+    fn file_name(&self, f: &FontInner) -> String {
+        let name = f.file_name.as_str().replace("\"", "");
+        if self.format == font_info::OutputFormat::Auto {
+            return name;
         }
-        Ok((String::new(), None))
+        let path = std::path::Path::new(&name);
+        let cff = path
+            .extension()
+            .map(|e| e.eq_ignore_ascii_case("otf"))
+            .unwrap_or(false);
+        path.with_extension(self.format.extension(cff))
+            .display()
+            .to_string()
     }
 
-    fn decode(encoding: &'static encoding_rs::Encoding, data: &[u8]) -> String {
-        let mut decoder = encoding.new_decoder();
-        if let Some(size) = decoder.max_utf8_buffer_length(data.len()) {
-            let mut s = String::with_capacity(size);
-            let (_res, _read, _repl) = decoder.decode_to_string(data, &mut s, true);
-            s
-        } else {
-            String::new() // can only happen if buffer is enormous
-        }
+    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+        let w = Window::new("subset").open(open).resizable(true);
+        w.show(ctx, |ui| {
+            self.ui(ui);
+        });
     }
 }
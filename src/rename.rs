@@ -0,0 +1,429 @@
+//! 重建 name 表，替换子集字体的名称
+//!
+//! 名称会按各记录原本的平台和编码重新编码，版权、许可证等其它记录保持不变
+
+use encoding_rs::{BIG5, EUC_KR, Encoding, GBK, MACINTOSH, SHIFT_JIS, X_MAC_CYRILLIC};
+
+use crate::font_info::BoxError;
+use crate::sfnt::read_u16;
+
+const FAMILY: u16 = 1;
+const SUBFAMILY: u16 = 2;
+const UNIQUE_ID: u16 = 3;
+const FULL_NAME: u16 = 4;
+const POSTSCRIPT_NAME: u16 = 6;
+const TYPOGRAPHIC_FAMILY: u16 = 16;
+const TYPOGRAPHIC_SUBFAMILY: u16 = 17;
+const COMPATIBLE_FULL: u16 = 18;
+const POSTSCRIPT_CID: u16 = 20;
+const WWS_FAMILY: u16 = 21;
+
+struct Record {
+    platform: u16,
+    encoding: u16,
+    language: u16,
+    name_id: u16,
+    data: Vec<u8>,
+}
+
+impl Record {
+    /// 是否按 UTF-16BE 编码
+    fn is_utf16(&self) -> bool {
+        self.platform == 0 || (self.platform == 3 && matches!(self.encoding, 0 | 1 | 10))
+    }
+
+    fn decode(&self) -> Option<String> {
+        if self.is_utf16() {
+            return Some(decode_utf16be(&self.data));
+        }
+        let encoding = legacy_encoding(self.platform, self.encoding)?;
+        let data = if self.platform == 3 {
+            // Windows 的双字节编码按 16 位存储，单字节字符的高位为0
+            self.data
+                .chunks_exact(2)
+                .flat_map(|c| if c[0] == 0 { &c[1..] } else { c })
+                .copied()
+                .collect::<Vec<_>>()
+        } else {
+            self.data.clone()
+        };
+        Some(encoding.decode_without_bom_handling(&data).0.into_owned())
+    }
+
+    /// 按本记录的平台和编码编码字符串，无法表示时返回 None
+    fn encode(&self, value: &str) -> Option<Vec<u8>> {
+        if self.is_utf16() {
+            return Some(value.encode_utf16().flat_map(|u| u.to_be_bytes()).collect());
+        }
+        match legacy_encoding(self.platform, self.encoding) {
+            Some(encoding) if self.platform == 3 => {
+                let mut data = Vec::new();
+                let mut buffer = [0; 4];
+                for c in value.chars() {
+                    let (bytes, _, had_errors) = encoding.encode(c.encode_utf8(&mut buffer));
+                    if had_errors || bytes.len() > 2 {
+                        return None;
+                    }
+                    let unit = bytes.iter().fold(0u16, |u, b| (u << 8) | u16::from(*b));
+                    data.extend_from_slice(&unit.to_be_bytes());
+                }
+                Some(data)
+            }
+            Some(encoding) => {
+                let (bytes, _, had_errors) = encoding.encode(value);
+                (!had_errors).then(|| bytes.into_owned())
+            }
+            // 其它 Mac 编码都兼容 ASCII
+            None if self.platform == 1 && value.is_ascii() => Some(value.as_bytes().to_vec()),
+            None => None,
+        }
+    }
+}
+
+/// Mac 和 Windows 平台的传统编码，encoding_rs 不支持的编码返回 None
+fn legacy_encoding(platform: u16, encoding: u16) -> Option<&'static Encoding> {
+    match (platform, encoding) {
+        (1, 0) => Some(MACINTOSH),
+        (1, 1) | (3, 2) => Some(SHIFT_JIS),
+        (1, 2) | (3, 4) => Some(BIG5),
+        (1, 3) | (3, 5) => Some(EUC_KR),
+        (1, 7) => Some(X_MAC_CYRILLIC),
+        (1, 25) | (3, 3) => Some(GBK),
+        _ => None,
+    }
+}
+
+/// 新的名称
+pub(crate) struct NewName<'a> {
+    /// 字体族名称，可以是任意语言
+    pub family: &'a str,
+    /// PostScript 名称使用的族名称，只能包含 ASCII
+    pub postscript_family: &'a str,
+}
+
+/// 替换 name 表中和字体名称相关的记录
+pub(crate) fn rename(name_table: &[u8], name: &NewName) -> Result<Vec<u8>, BoxError> {
+    let Names {
+        version,
+        records,
+        lang_tags,
+    } = read_records(name_table)?;
+
+    let postscript = postscript_name(name.postscript_family, &subfamily_of(&records, None));
+    let mut renamed = Vec::with_capacity(records.len());
+    for record in records.iter() {
+        let value = match record.name_id {
+            FAMILY | TYPOGRAPHIC_FAMILY | WWS_FAMILY => name.family.to_string(),
+            FULL_NAME | COMPATIBLE_FULL => {
+                full_name(name.family, &subfamily_of(&records, Some(record)))
+            }
+            POSTSCRIPT_NAME | POSTSCRIPT_CID => postscript.clone(),
+            UNIQUE_ID => format!("fontview:{postscript}"),
+            _ => {
+                renamed.push(Record {
+                    data: record.data.clone(),
+                    ..*record
+                });
+                continue;
+            }
+        };
+        // 无法用原编码表示的记录直接删除，避免留下旧名称
+        if let Some(data) = record.encode(&value) {
+            renamed.push(Record { data, ..*record });
+        }
+    }
+    renamed.sort_by_key(|r| (r.platform, r.encoding, r.language, r.name_id));
+
+    let header_len = 6
+        + renamed.len() * 12
+        + if version == 1 {
+            2 + lang_tags.len() * 4
+        } else {
+            0
+        };
+    let mut out = Vec::new();
+    let mut storage = Vec::new();
+    out.extend_from_slice(&version.to_be_bytes());
+    out.extend_from_slice(&(renamed.len() as u16).to_be_bytes());
+    out.extend_from_slice(&(header_len as u16).to_be_bytes());
+    for r in renamed.iter() {
+        let offset = store(&mut storage, &r.data)?;
+        out.extend_from_slice(&r.platform.to_be_bytes());
+        out.extend_from_slice(&r.encoding.to_be_bytes());
+        out.extend_from_slice(&r.language.to_be_bytes());
+        out.extend_from_slice(&r.name_id.to_be_bytes());
+        out.extend_from_slice(&(r.data.len() as u16).to_be_bytes());
+        out.extend_from_slice(&offset.to_be_bytes());
+    }
+    if version == 1 {
+        out.extend_from_slice(&(lang_tags.len() as u16).to_be_bytes());
+        for t in lang_tags.iter() {
+            let offset = store(&mut storage, t)?;
+            out.extend_from_slice(&(t.len() as u16).to_be_bytes());
+            out.extend_from_slice(&offset.to_be_bytes());
+        }
+    }
+    out.extend_from_slice(&storage);
+    Ok(out)
+}
+
+/// 解析后的 name 表
+struct Names {
+    version: u16,
+    records: Vec<Record>,
+    /// 版本1的语言标签
+    lang_tags: Vec<Vec<u8>>,
+}
+
+/// 读取 name 表的所有记录
+fn read_records(name_table: &[u8]) -> Result<Names, BoxError> {
+    let version = read_u16(name_table, 0)?;
+    let count = usize::from(read_u16(name_table, 2)?);
+    let storage = usize::from(read_u16(name_table, 4)?);
+
+    let string = |offset: usize, length: usize| -> Result<Vec<u8>, BoxError> {
+        name_table
+            .get(storage + offset..storage + offset + length)
+            .map(|s| s.to_vec())
+            .ok_or_else(|| "name record out of bounds".into())
+    };
+
+    let mut records = Vec::with_capacity(count);
+    for i in 0..count {
+        let r = 6 + i * 12;
+        records.push(Record {
+            platform: read_u16(name_table, r)?,
+            encoding: read_u16(name_table, r + 2)?,
+            language: read_u16(name_table, r + 4)?,
+            name_id: read_u16(name_table, r + 6)?,
+            data: string(
+                usize::from(read_u16(name_table, r + 10)?),
+                usize::from(read_u16(name_table, r + 8)?),
+            )?,
+        });
+    }
+    // 版本1的语言标签
+    let mut lang_tags = Vec::new();
+    if version == 1 {
+        let r = 6 + count * 12;
+        let lang_tag_count = usize::from(read_u16(name_table, r)?);
+        for i in 0..lang_tag_count {
+            let t = r + 2 + i * 4;
+            lang_tags.push(string(
+                usize::from(read_u16(name_table, t + 2)?),
+                usize::from(read_u16(name_table, t)?),
+            )?);
+        }
+    }
+    Ok(Names {
+        version,
+        records,
+        lang_tags,
+    })
+}
+
+/// 字体族和子字体族名称，优先使用 16、17 号英文记录
+pub(crate) fn family_names(name_table: &[u8]) -> Result<(String, String), BoxError> {
+    let records = read_records(name_table)?.records;
+    let family = english(&records, TYPOGRAPHIC_FAMILY)
+        .or_else(|| english(&records, FAMILY))
+        .unwrap_or_default();
+    Ok((family, subfamily_of(&records, None)))
+}
+
+/// 所有记录解码后的字符串
+pub(crate) fn records(name_table: &[u8]) -> Result<Vec<crate::inspect::NameRecord>, BoxError> {
+    let names = read_records(name_table)?;
+    let lang_tags = names
+        .lang_tags
+        .iter()
+        .map(|t| decode_utf16be(t))
+        .collect::<Vec<_>>();
+    Ok(names
+        .records
+        .iter()
+        .map(|r| crate::inspect::NameRecord {
+            platform: r.platform,
+            encoding: r.encoding,
+            language: r.language,
+            language_tag: crate::language::tag(r.platform, r.language, &lang_tags),
+            name_id: r.name_id,
+            value: r.decode(),
+        })
+        .collect())
+}
+
+/// 写入字符串存储区，相同的字符串只保存一份
+fn store(storage: &mut Vec<u8>, data: &[u8]) -> Result<u16, BoxError> {
+    let offset = if data.is_empty() {
+        Some(0)
+    } else {
+        storage.windows(data.len()).position(|w| w == data)
+    };
+    let offset = offset.unwrap_or_else(|| {
+        storage.extend_from_slice(data);
+        storage.len() - data.len()
+    });
+    if storage.len() > usize::from(u16::MAX) {
+        return Err("name table too large".into());
+    }
+    Ok(offset as u16)
+}
+
+/// 查找子字体族名称，优先使用和 record 相同平台、编码、语言的记录，其次是英文记录
+fn subfamily_of(records: &[Record], record: Option<&Record>) -> String {
+    let find = |name_id: u16| {
+        let same = records.iter().find(|r| {
+            r.name_id == name_id
+                && record.is_some_and(|o| {
+                    r.platform == o.platform && r.encoding == o.encoding && r.language == o.language
+                })
+        });
+        same.and_then(|r| r.decode())
+            .or_else(|| english(records, name_id))
+    };
+    find(TYPOGRAPHIC_SUBFAMILY)
+        .or_else(|| find(SUBFAMILY))
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "Regular".to_string())
+}
+
+/// 查找 Windows 或 Mac 平台的英文记录
+fn english(records: &[Record], name_id: u16) -> Option<String> {
+    records
+        .iter()
+        .find(|r| {
+            r.name_id == name_id
+                && ((r.platform == 3 && r.language == 0x409)
+                    || (r.platform == 1 && r.language == 0))
+        })
+        .and_then(|r| r.decode())
+}
+
+fn full_name(family: &str, subfamily: &str) -> String {
+    if subfamily.eq_ignore_ascii_case("Regular") {
+        family.to_string()
+    } else {
+        format!("{family} {subfamily}")
+    }
+}
+
+/// PostScript 名称只能包含可打印 ASCII，并且不能超过63个字符
+fn postscript_name(family: &str, subfamily: &str) -> String {
+    let clean = |s: &str| {
+        s.chars()
+            .filter(|c| c.is_ascii_graphic() && !"[](){}<>/%".contains(*c))
+            .collect::<String>()
+    };
+    let subfamily = clean(subfamily);
+    let mut name = if subfamily.is_empty() {
+        clean(family)
+    } else {
+        format!("{}-{}", clean(family), subfamily)
+    };
+    name.truncate(63);
+    name
+}
+
+fn decode_utf16be(data: &[u8]) -> String {
+    let units = data
+        .chunks_exact(2)
+        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+        .collect::<Vec<_>>();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按给定的原始字节生成版本0的 name 表
+    fn name_table(records: &[(u16, u16, u16, u16, Vec<u8>)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut storage = Vec::new();
+        out.extend_from_slice(&0u16.to_be_bytes());
+        out.extend_from_slice(&(records.len() as u16).to_be_bytes());
+        out.extend_from_slice(&(6 + records.len() as u16 * 12).to_be_bytes());
+        for (platform, encoding, language, name_id, data) in records {
+            for v in [*platform, *encoding, *language, *name_id, data.len() as u16] {
+                out.extend_from_slice(&v.to_be_bytes());
+            }
+            out.extend_from_slice(&(storage.len() as u16).to_be_bytes());
+            storage.extend_from_slice(data);
+        }
+        out.extend_from_slice(&storage);
+        out
+    }
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(|u| u.to_be_bytes()).collect()
+    }
+
+    /// 指定平台、编码和编号的记录解码后的值
+    fn value(table: &[u8], platform: u16, encoding: u16, name_id: u16) -> Option<String> {
+        records(table)
+            .unwrap()
+            .into_iter()
+            .find(|r| r.platform == platform && r.encoding == encoding && r.name_id == name_id)
+            .and_then(|r| r.value)
+    }
+
+    fn font() -> Vec<u8> {
+        name_table(&[
+            (1, 0, 0, 1, b"Old".to_vec()),
+            (1, 0, 0, 2, b"Bold".to_vec()),
+            (3, 1, 0x409, 0, utf16("Copyright")),
+            (3, 1, 0x409, 1, utf16("Old")),
+            (3, 1, 0x409, 2, utf16("Bold")),
+            (3, 1, 0x409, 4, utf16("Old Bold")),
+            (3, 1, 0x409, 6, utf16("Old-Bold")),
+            // GBK 按16位存储，ASCII 字符高位为0
+            (3, 3, 0x804, 1, vec![0xbe, 0xc9, 0x00, 0x41]),
+        ])
+    }
+
+    #[test]
+    fn decode_legacy_records() {
+        let table = font();
+        assert_eq!(value(&table, 3, 3, 1).as_deref(), Some("旧A"));
+        assert_eq!(value(&table, 1, 0, 1).as_deref(), Some("Old"));
+        assert_eq!(
+            family_names(&table).unwrap(),
+            ("Old".to_string(), "Bold".to_string())
+        );
+    }
+
+    #[test]
+    fn rename_reencodes_records() {
+        let name = NewName {
+            family: "新字体",
+            postscript_family: "NewFont",
+        };
+        let table = rename(&font(), &name).unwrap();
+        assert_eq!(value(&table, 3, 1, 1).as_deref(), Some("新字体"));
+        assert_eq!(value(&table, 3, 1, 4).as_deref(), Some("新字体 Bold"));
+        assert_eq!(value(&table, 3, 1, 6).as_deref(), Some("NewFont-Bold"));
+        assert_eq!(value(&table, 3, 1, 0).as_deref(), Some("Copyright"));
+        assert_eq!(value(&table, 3, 3, 1).as_deref(), Some("新字体"));
+        // Mac Roman 无法表示中文，旧名称被删除，子字体族保持不变
+        assert_eq!(value(&table, 1, 0, 1), None);
+        assert_eq!(value(&table, 1, 0, 2).as_deref(), Some("Bold"));
+
+        let gbk = read_records(&table)
+            .unwrap()
+            .records
+            .into_iter()
+            .find(|r| r.platform == 3 && r.encoding == 3)
+            .unwrap();
+        assert_eq!(gbk.data, [0xd0, 0xc2, 0xd7, 0xd6, 0xcc, 0xe5]);
+    }
+
+    #[test]
+    fn postscript_name_is_ascii() {
+        assert_eq!(
+            postscript_name("My Font (Web)", "Bold Italic"),
+            "MyFontWeb-BoldItalic"
+        );
+        assert_eq!(postscript_name(&"a".repeat(80), "Regular").len(), 63);
+    }
+}
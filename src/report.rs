@@ -0,0 +1,67 @@
+//! 机器可读的子集化报告
+
+use serde_json::{Map, Value, json};
+
+use crate::batch::Summary;
+
+/// 报告格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReportFormat {
+    Json,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("unknown report format {s}, expect json")),
+        }
+    }
+}
+
+/// 生成 JSON 报告，每个字体一项，失败的字体包含 error
+pub(crate) fn json(summaries: &[Summary]) -> String {
+    let fonts = summaries
+        .iter()
+        .map(|s| {
+            let mut font = json!({
+                "input": s.input.display().to_string(),
+                "face": s.face,
+                "output": s.output.as_ref().map(|o| o.display().to_string()),
+                "missing": s.missing.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+                "missing_code_points": s
+                    .missing
+                    .iter()
+                    .map(|c| format!("U+{:04X}", *c as u32))
+                    .collect::<Vec<_>>(),
+            });
+            let extra = match &s.result {
+                Ok(r) => json!({
+                    "ok": true,
+                    "family": r.family,
+                    "format": r.format,
+                    "glyphs": { "original": r.original_glyphs, "subset": r.glyphs },
+                    "characters": r.chars,
+                    "size": { "input": s.input_size, "output": r.size },
+                    "tables": r
+                        .tables
+                        .iter()
+                        .map(|(tag, len)| (fontview::font_info::tag_display(*tag), json!(len)))
+                        .collect::<Map<_, _>>(),
+                }),
+                Err(e) => json!({
+                    "ok": false,
+                    "error": e.to_string(),
+                    "size": { "input": s.input_size, "output": Value::Null },
+                }),
+            };
+            if let (Value::Object(font), Value::Object(extra)) = (&mut font, extra) {
+                font.extend(extra);
+            }
+            font
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_string_pretty(&json!({ "fonts": fonts })).unwrap_or_default()
+}
@@ -0,0 +1,259 @@
+//! sfnt（TrueType/OpenType）容器的底层读写
+
+use std::borrow::Cow;
+
+use crate::font_info::BoxError;
+
+/// 由4个字节生成表标签
+pub(crate) const fn tag(b: &[u8; 4]) -> u32 {
+    u32::from_be_bytes(*b)
+}
+
+pub(crate) const TAG_HEAD: u32 = tag(b"head");
+pub(crate) const TAG_GLYF: u32 = tag(b"glyf");
+pub(crate) const TAG_LOCA: u32 = tag(b"loca");
+/// 整个字体的校验和加上 checkSumAdjustment 应当等于该值
+const CHECKSUM_MAGIC: u32 = 0xB1B0AFBA;
+
+/// 单个表
+pub(crate) struct Table<'a> {
+    pub tag: u32,
+    pub data: Cow<'a, [u8]>,
+}
+
+/// 单个字体的表目录
+pub(crate) struct Sfnt<'a> {
+    pub flavor: u32,
+    pub tables: Vec<Table<'a>>,
+}
+
+impl<'a> Sfnt<'a> {
+    /// 读取文件开头的字体
+    pub(crate) fn parse(data: &'a [u8]) -> Result<Self, BoxError> {
+        Self::parse_at(data, 0)
+    }
+
+    /// 读取指定偏移处的 offset table，ttc 中各表的偏移同样是相对文件开头
+    pub(crate) fn parse_at(data: &'a [u8], offset: usize) -> Result<Self, BoxError> {
+        let flavor = read_u32(data, offset)?;
+        let num_tables = usize::from(read_u16(data, offset + 4)?);
+        let mut tables = Vec::with_capacity(num_tables);
+        for i in 0..num_tables {
+            let record = offset + 12 + i * 16;
+            let tag = read_u32(data, record)?;
+            let start = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            let table = data
+                .get(start..start + length)
+                .ok_or_else(|| format!("table {} out of bounds", tag_display(tag)))?;
+            tables.push(Table {
+                tag,
+                data: Cow::Borrowed(table),
+            });
+        }
+        Ok(Sfnt { flavor, tables })
+    }
+
+    pub(crate) fn table(&self, tag: u32) -> Option<&[u8]> {
+        self.tables
+            .iter()
+            .find(|t| t.tag == tag)
+            .map(|t| t.data.as_ref())
+    }
+
+    /// 替换表，不存在时添加
+    pub(crate) fn set_table(&mut self, tag: u32, data: Vec<u8>) {
+        match self.tables.iter_mut().find(|t| t.tag == tag) {
+            Some(t) => t.data = Cow::Owned(data),
+            None => self.tables.push(Table {
+                tag,
+                data: Cow::Owned(data),
+            }),
+        }
+    }
+
+    /// 重新生成字体文件，表按标签排序并4字节对齐
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut tables = self.tables.iter().collect::<Vec<_>>();
+        tables.sort_by_key(|t| t.tag);
+
+        let header_len = 12 + 16 * tables.len();
+        let mut body = Vec::new();
+        let mut records = Vec::with_capacity(tables.len());
+        for t in tables {
+            records.push(TableRecord {
+                tag: t.tag,
+                checksum: checksum(t.tag, &t.data),
+                offset: (header_len + body.len()) as u32,
+                length: t.data.len() as u32,
+            });
+            body.extend_from_slice(&t.data);
+            body.resize(pad4(body.len()), 0);
+        }
+
+        let mut out = Vec::with_capacity(header_len + body.len());
+        write_directory(&mut out, self.flavor, &records);
+        out.extend_from_slice(&body);
+
+        // 整个文件的校验和需要在 checkSumAdjustment 为0时计算
+        if let Some(head) = records.iter().find(|r| r.tag == TAG_HEAD && r.length >= 12) {
+            let adjustment = head.offset as usize + 8;
+            out[adjustment..adjustment + 4].fill(0);
+            let sum = checksum(0, &out);
+            out[adjustment..adjustment + 4]
+                .copy_from_slice(&CHECKSUM_MAGIC.wrapping_sub(sum).to_be_bytes());
+        }
+        out
+    }
+}
+
+/// 校验表目录中每个表的校验和，单个字体还会校验 head.checkSumAdjustment
+pub(crate) fn verify(data: &[u8]) -> Result<(), BoxError> {
+    let offsets = if data.starts_with(b"ttcf") {
+        (0..read_u32(data, 8)? as usize)
+            .map(|i| read_u32(data, 12 + 4 * i).map(|o| o as usize))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        vec![0]
+    };
+    for offset in offsets.iter() {
+        let num_tables = usize::from(read_u16(data, offset + 4)?);
+        for i in 0..num_tables {
+            let record = offset + 12 + i * 16;
+            let tag = read_u32(data, record)?;
+            let expected = read_u32(data, record + 4)?;
+            let start = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            let table = data
+                .get(start..start + length)
+                .ok_or_else(|| format!("table {} out of bounds", tag_display(tag)))?;
+            if checksum(tag, table) != expected {
+                return Err(format!("checksum mismatch in table {}", tag_display(tag)).into());
+            }
+        }
+    }
+    if offsets.len() == 1 && checksum(0, data) != CHECKSUM_MAGIC {
+        return Err("head.checkSumAdjustment mismatch".into());
+    }
+    Ok(())
+}
+
+/// 把多个 sfnt 字体打包为字体集合（ttc），内容相同的表只保存一份
+pub(crate) fn build_collection(fonts: &[Vec<u8>]) -> Result<Vec<u8>, BoxError> {
+    let fonts = fonts
+        .iter()
+        .map(|f| Sfnt::parse(f))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let header_len = 12 + 4 * fonts.len();
+    let directory_len = fonts
+        .iter()
+        .map(|f| 12 + 16 * f.tables.len())
+        .sum::<usize>();
+
+    let mut directories = Vec::with_capacity(directory_len);
+    let mut offsets = Vec::with_capacity(fonts.len());
+    let mut body = Vec::new();
+    let mut stored: Vec<(&[u8], u32)> = Vec::new();
+    for font in fonts.iter() {
+        offsets.push((header_len + directories.len()) as u32);
+        let mut tables = font.tables.iter().collect::<Vec<_>>();
+        tables.sort_by_key(|t| t.tag);
+        let mut records = Vec::with_capacity(tables.len());
+        for t in tables {
+            let offset = match stored.iter().find(|(d, _)| *d == t.data.as_ref()) {
+                Some((_, offset)) => *offset,
+                None => {
+                    let offset = (header_len + directory_len + body.len()) as u32;
+                    body.extend_from_slice(&t.data);
+                    body.resize(pad4(body.len()), 0);
+                    stored.push((t.data.as_ref(), offset));
+                    offset
+                }
+            };
+            records.push(TableRecord {
+                tag: t.tag,
+                checksum: checksum(t.tag, &t.data),
+                offset,
+                length: t.data.len() as u32,
+            });
+        }
+        write_directory(&mut directories, font.flavor, &records);
+    }
+
+    let mut out = Vec::with_capacity(header_len + directory_len + body.len());
+    out.extend_from_slice(b"ttcf");
+    out.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+    out.extend_from_slice(&(fonts.len() as u32).to_be_bytes());
+    for offset in offsets {
+        out.extend_from_slice(&offset.to_be_bytes());
+    }
+    out.extend_from_slice(&directories);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// 表目录中的一条记录
+pub(crate) struct TableRecord {
+    pub tag: u32,
+    pub checksum: u32,
+    pub offset: u32,
+    pub length: u32,
+}
+
+/// 写入 offset table 和表目录
+pub(crate) fn write_directory(out: &mut Vec<u8>, flavor: u32, records: &[TableRecord]) {
+    let num_tables = records.len() as u16;
+    let mut entry_selector = 0u16;
+    while (1u32 << (entry_selector + 1)) <= u32::from(num_tables) {
+        entry_selector += 1;
+    }
+    let search_range = (1u16 << entry_selector) * 16;
+    out.extend_from_slice(&flavor.to_be_bytes());
+    out.extend_from_slice(&num_tables.to_be_bytes());
+    out.extend_from_slice(&search_range.to_be_bytes());
+    out.extend_from_slice(&entry_selector.to_be_bytes());
+    out.extend_from_slice(&(num_tables * 16 - search_range).to_be_bytes());
+    for r in records {
+        out.extend_from_slice(&r.tag.to_be_bytes());
+        out.extend_from_slice(&r.checksum.to_be_bytes());
+        out.extend_from_slice(&r.offset.to_be_bytes());
+        out.extend_from_slice(&r.length.to_be_bytes());
+    }
+}
+
+/// 表校验和，head 表需要将 checkSumAdjustment 视为0
+pub(crate) fn checksum(tag: u32, data: &[u8]) -> u32 {
+    let mut sum = 0u32;
+    for (i, chunk) in data.chunks(4).enumerate() {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        if tag == TAG_HEAD && i == 2 {
+            continue;
+        }
+        sum = sum.wrapping_add(u32::from_be_bytes(word));
+    }
+    sum
+}
+
+/// 4字节对齐后的长度
+pub(crate) fn pad4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// 表名，例如 `OS/2`
+pub fn tag_display(tag: u32) -> String {
+    String::from_utf8_lossy(&tag.to_be_bytes()).into_owned()
+}
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Result<u16, BoxError> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| "unexpected end of font data".into())
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Result<u32, BoxError> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "unexpected end of font data".into())
+}
@@ -0,0 +1,322 @@
+//! Unicode 变体序列（cmap format 14）
+//!
+//! 异体字选择符（IVS，U+E0100..U+E01EF）和 U+FE00..U+FE0F 等选择符需要和前一个字符组成序列，
+//! 通过 format 14 子表才能找到对应的字形
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::font_info::BoxError;
+use crate::sfnt::{read_u16, read_u32};
+
+/// 是否是变体选择符
+pub fn is_selector(c: char) -> bool {
+    matches!(c as u32, 0x180b..=0x180d | 0x180f | 0xfe00..=0xfe0f | 0xe0100..=0xe01ef)
+}
+
+/// 变体序列对应的字形
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Variant {
+    /// 使用基础字符在 cmap 中的默认字形
+    Default,
+    Glyph(u16),
+}
+
+/// 一个选择符的所有序列
+#[derive(Debug, Default)]
+struct SelectorRecord {
+    /// 使用默认字形的基础字符
+    default: Vec<u32>,
+    /// 使用指定字形的基础字符
+    non_default: Vec<(u32, u16)>,
+}
+
+/// cmap format 14 子表
+#[derive(Debug, Default)]
+pub(crate) struct Sequences {
+    records: BTreeMap<u32, SelectorRecord>,
+}
+
+impl Sequences {
+    /// 读取 cmap 表中的 format 14 子表，没有时返回空
+    pub(crate) fn parse(cmap: &[u8]) -> Result<Self, BoxError> {
+        let mut sequences = Sequences::default();
+        let num_tables = usize::from(read_u16(cmap, 2)?);
+        for i in 0..num_tables {
+            let record = 4 + i * 8;
+            let offset = read_u32(cmap, record + 4)? as usize;
+            if read_u16(cmap, record)? != 0
+                || read_u16(cmap, record + 2)? != 5
+                || read_u16(cmap, offset)? != 14
+            {
+                continue;
+            }
+            let count = read_u32(cmap, offset + 6)? as usize;
+            for r in 0..count {
+                let r = offset + 10 + r * 11;
+                let selector = read_u24(cmap, r)?;
+                let default_offset = read_u32(cmap, r + 3)? as usize;
+                let non_default_offset = read_u32(cmap, r + 7)? as usize;
+                let record = sequences.records.entry(selector).or_default();
+                if default_offset != 0 {
+                    let d = offset + default_offset;
+                    for n in 0..read_u32(cmap, d)? as usize {
+                        let range = d + 4 + n * 4;
+                        let start = read_u24(cmap, range)?;
+                        let additional = *cmap.get(range + 3).ok_or("unexpected end of cmap")?;
+                        record.default.extend(start..=start + u32::from(additional));
+                    }
+                }
+                if non_default_offset != 0 {
+                    let m = offset + non_default_offset;
+                    for n in 0..read_u32(cmap, m)? as usize {
+                        let mapping = m + 4 + n * 5;
+                        record
+                            .non_default
+                            .push((read_u24(cmap, mapping)?, read_u16(cmap, mapping + 3)?));
+                    }
+                }
+            }
+            break;
+        }
+        Ok(sequences)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// 查找序列对应的字形，字体不支持该序列时返回 None
+    pub(crate) fn lookup(&self, base: char, selector: char) -> Option<Variant> {
+        let record = self.records.get(&(selector as u32))?;
+        let base = base as u32;
+        if let Ok(i) = record.non_default.binary_search_by_key(&base, |(c, _)| *c) {
+            return Some(Variant::Glyph(record.non_default[i].1));
+        }
+        record
+            .default
+            .binary_search(&base)
+            .ok()
+            .map(|_| Variant::Default)
+    }
+
+    /// 只保留子集中仍然有效的序列，并把字形转换为子集中的新编号
+    ///
+    /// 使用默认字形的序列要求基础字符在 chars 中，指定字形的序列要求字形在 glyph_map 中
+    pub(crate) fn retain(&self, chars: &[char], glyph_map: &HashMap<u16, u16>) -> Sequences {
+        let mut chars = chars.iter().map(|c| *c as u32).collect::<Vec<_>>();
+        chars.sort();
+        let mut records = BTreeMap::new();
+        for (selector, record) in self.records.iter() {
+            let kept = SelectorRecord {
+                default: record
+                    .default
+                    .iter()
+                    .copied()
+                    .filter(|c| chars.binary_search(c).is_ok())
+                    .collect(),
+                non_default: record
+                    .non_default
+                    .iter()
+                    .filter_map(|(c, g)| glyph_map.get(g).map(|g| (*c, *g)))
+                    .collect(),
+            };
+            if !kept.default.is_empty() || !kept.non_default.is_empty() {
+                records.insert(*selector, kept);
+            }
+        }
+        Sequences { records }
+    }
+
+    /// 生成 format 14 子表
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let header_len = 10 + 11 * self.records.len();
+        let mut header = Vec::with_capacity(header_len);
+        let mut body = Vec::new();
+        for (selector, record) in self.records.iter() {
+            header.extend_from_slice(&selector.to_be_bytes()[1..]);
+
+            if record.default.is_empty() {
+                header.extend_from_slice(&0u32.to_be_bytes());
+            } else {
+                header.extend_from_slice(&((header_len + body.len()) as u32).to_be_bytes());
+                // 连续的字符合并为一个范围，每个范围最多256个字符
+                let mut ranges: Vec<(u32, u8)> = Vec::new();
+                for c in record.default.iter() {
+                    match ranges.last_mut() {
+                        Some((start, additional))
+                            if *additional < u8::MAX
+                                && *start + u32::from(*additional) + 1 == *c =>
+                        {
+                            *additional += 1
+                        }
+                        _ => ranges.push((*c, 0)),
+                    }
+                }
+                body.extend_from_slice(&(ranges.len() as u32).to_be_bytes());
+                for (start, additional) in ranges {
+                    body.extend_from_slice(&start.to_be_bytes()[1..]);
+                    body.push(additional);
+                }
+            }
+
+            if record.non_default.is_empty() {
+                header.extend_from_slice(&0u32.to_be_bytes());
+            } else {
+                header.extend_from_slice(&((header_len + body.len()) as u32).to_be_bytes());
+                body.extend_from_slice(&(record.non_default.len() as u32).to_be_bytes());
+                for (c, g) in record.non_default.iter() {
+                    body.extend_from_slice(&c.to_be_bytes()[1..]);
+                    body.extend_from_slice(&g.to_be_bytes());
+                }
+            }
+        }
+
+        let mut out = Vec::with_capacity(header_len + body.len());
+        out.extend_from_slice(&14u16.to_be_bytes());
+        out.extend_from_slice(&((header_len + body.len()) as u32).to_be_bytes());
+        out.extend_from_slice(&(self.records.len() as u32).to_be_bytes());
+        out.extend_from_slice(&header);
+        out.extend_from_slice(&body);
+        out
+    }
+}
+
+/// 把 format 14 子表以 (0, 5) 编码记录加入 cmap 表，替换已有的 format 14 子表
+pub(crate) fn with_sequences(cmap: &[u8], sequences: &Sequences) -> Result<Vec<u8>, BoxError> {
+    let version = read_u16(cmap, 0)?;
+    let num_tables = usize::from(read_u16(cmap, 2)?);
+    let mut records = Vec::with_capacity(num_tables + 1);
+    for i in 0..num_tables {
+        let record = 4 + i * 8;
+        let platform = read_u16(cmap, record)?;
+        let encoding = read_u16(cmap, record + 2)?;
+        if (platform, encoding) == (0, 5) {
+            continue;
+        }
+        let offset = read_u32(cmap, record + 4)? as usize;
+        let data = cmap
+            .get(offset..offset + subtable_len(cmap, offset)?)
+            .ok_or("cmap subtable out of bounds")?;
+        records.push((platform, encoding, data));
+    }
+    let format14 = sequences.to_bytes();
+    if !sequences.is_empty() {
+        records.push((0, 5, format14.as_slice()));
+    }
+    records.sort_by_key(|(platform, encoding, _)| (*platform, *encoding));
+
+    let header_len = 4 + 8 * records.len();
+    let mut out = Vec::new();
+    let mut body: Vec<u8> = Vec::new();
+    // 多个编码记录可以共用同一个子表
+    let mut stored: Vec<(&[u8], u32)> = Vec::new();
+    out.extend_from_slice(&version.to_be_bytes());
+    out.extend_from_slice(&(records.len() as u16).to_be_bytes());
+    for (platform, encoding, data) in records.iter() {
+        let offset = match stored.iter().find(|(d, _)| d == data) {
+            Some((_, offset)) => *offset,
+            None => {
+                let offset = (header_len + body.len()) as u32;
+                body.extend_from_slice(data);
+                body.resize(crate::sfnt::pad4(body.len()), 0);
+                stored.push((data, offset));
+                offset
+            }
+        };
+        out.extend_from_slice(&platform.to_be_bytes());
+        out.extend_from_slice(&encoding.to_be_bytes());
+        out.extend_from_slice(&offset.to_be_bytes());
+    }
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// 根据子表格式读取子表长度
+fn subtable_len(cmap: &[u8], offset: usize) -> Result<usize, BoxError> {
+    match read_u16(cmap, offset)? {
+        0 | 2 | 4 | 6 => Ok(usize::from(read_u16(cmap, offset + 2)?)),
+        8 | 10 | 12 | 13 => Ok(read_u32(cmap, offset + 4)? as usize),
+        14 => Ok(read_u32(cmap, offset + 2)? as usize),
+        format => Err(format!("unknown cmap subtable format {format}").into()),
+    }
+}
+
+fn read_u24(data: &[u8], offset: usize) -> Result<u32, BoxError> {
+    data.get(offset..offset + 3)
+        .map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]))
+        .ok_or_else(|| "unexpected end of cmap".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 没有任何子表的 cmap
+    const EMPTY_CMAP: &[u8] = &[0, 0, 0, 0];
+
+    fn sequences() -> Sequences {
+        let mut records = BTreeMap::new();
+        records.insert(
+            0xfe00,
+            SelectorRecord {
+                default: (0x4e00..=0x4e02).chain([0x4e10]).collect(),
+                non_default: vec![],
+            },
+        );
+        records.insert(
+            0xe0100,
+            SelectorRecord {
+                default: vec![0x845b],
+                non_default: vec![(0x8fbb, 7), (0x908a, 9)],
+            },
+        );
+        Sequences { records }
+    }
+
+    #[test]
+    fn format14_round_trip() {
+        let cmap = with_sequences(EMPTY_CMAP, &sequences()).unwrap();
+        assert_eq!(read_u16(&cmap, 2).unwrap(), 1);
+        let parsed = Sequences::parse(&cmap).unwrap();
+        assert_eq!(
+            parsed.lookup('\u{4e01}', '\u{fe00}'),
+            Some(Variant::Default)
+        );
+        assert_eq!(
+            parsed.lookup('\u{4e10}', '\u{fe00}'),
+            Some(Variant::Default)
+        );
+        assert_eq!(parsed.lookup('\u{4e03}', '\u{fe00}'), None);
+        assert_eq!(parsed.lookup('葛', '\u{e0100}'), Some(Variant::Default));
+        assert_eq!(parsed.lookup('辻', '\u{e0100}'), Some(Variant::Glyph(7)));
+        assert_eq!(parsed.lookup('辻', '\u{e0101}'), None);
+    }
+
+    #[test]
+    fn retain_remaps_glyphs() {
+        let glyph_map = HashMap::from([(9, 2)]);
+        let retained = sequences().retain(&['\u{4e00}', '辻'], &glyph_map);
+        let cmap = with_sequences(EMPTY_CMAP, &retained).unwrap();
+        // 再次写入时替换已有的 format 14 子表
+        let cmap = with_sequences(&cmap, &retained).unwrap();
+        assert_eq!(read_u16(&cmap, 2).unwrap(), 1);
+
+        let parsed = Sequences::parse(&cmap).unwrap();
+        assert_eq!(
+            parsed.lookup('\u{4e00}', '\u{fe00}'),
+            Some(Variant::Default)
+        );
+        assert_eq!(parsed.lookup('\u{4e01}', '\u{fe00}'), None);
+        // 辻 的字形不在子集中，邊 的字形重新编号
+        assert_eq!(parsed.lookup('辻', '\u{e0100}'), None);
+        assert_eq!(parsed.lookup('邊', '\u{e0100}'), Some(Variant::Glyph(2)));
+        assert_eq!(parsed.lookup('葛', '\u{e0100}'), None);
+    }
+
+    #[test]
+    fn selectors() {
+        assert!(is_selector('\u{fe0f}'));
+        assert!(is_selector('\u{e0100}'));
+        assert!(!is_selector('a'));
+    }
+}
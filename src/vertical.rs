@@ -0,0 +1,63 @@
+//! 竖排度量表（vhea、vmtx、VORG）的子集化
+//!
+//! allsorts 不会输出这些表，需要按子集的字形编号重新生成
+
+use crate::font_info::BoxError;
+use crate::sfnt::read_u16;
+
+/// vhea 中 numOfLongVerMetrics 的偏移
+const NUM_LONG_METRICS: usize = 34;
+
+/// 按子集字形重新生成 vhea 和 vmtx，glyph_ids 的下标即新的字形编号
+///
+/// 所有字形都写为完整的 longVerMetric
+pub(crate) fn subset_vmtx(
+    vhea: &[u8],
+    vmtx: &[u8],
+    glyph_ids: &[u16],
+) -> Result<(Vec<u8>, Vec<u8>), BoxError> {
+    let long_metrics = usize::from(read_u16(vhea, NUM_LONG_METRICS)?);
+    if long_metrics == 0 {
+        return Err("vhea has no long vertical metrics".into());
+    }
+    let mut new_vmtx = Vec::with_capacity(glyph_ids.len() * 4);
+    for id in glyph_ids.iter().map(|id| usize::from(*id)) {
+        // 超过 numOfLongVerMetrics 的字形使用最后一个 advanceHeight
+        let advance = read_u16(vmtx, id.min(long_metrics - 1) * 4)?;
+        let top_side_bearing = if id < long_metrics {
+            read_u16(vmtx, id * 4 + 2)?
+        } else {
+            read_u16(vmtx, long_metrics * 4 + (id - long_metrics) * 2)?
+        };
+        new_vmtx.extend_from_slice(&advance.to_be_bytes());
+        new_vmtx.extend_from_slice(&top_side_bearing.to_be_bytes());
+    }
+
+    let mut new_vhea = vhea
+        .get(..NUM_LONG_METRICS + 2)
+        .ok_or("unexpected end of font data")?
+        .to_vec();
+    new_vhea[NUM_LONG_METRICS..].copy_from_slice(&(glyph_ids.len() as u16).to_be_bytes());
+    Ok((new_vhea, new_vmtx))
+}
+
+/// 按子集字形重新生成 VORG，只保留子集中字形的记录
+pub(crate) fn subset_vorg(vorg: &[u8], glyph_ids: &[u16]) -> Result<Vec<u8>, BoxError> {
+    let count = usize::from(read_u16(vorg, 6)?);
+    let mut records = Vec::new();
+    for i in 0..count {
+        let old = read_u16(vorg, 8 + i * 4)?;
+        if let Some(new) = glyph_ids.iter().position(|id| *id == old) {
+            records.push((new as u16, read_u16(vorg, 8 + i * 4 + 2)?));
+        }
+    }
+    records.sort();
+
+    let mut out = vorg.get(..6).ok_or("unexpected end of font data")?.to_vec();
+    out.extend_from_slice(&(records.len() as u16).to_be_bytes());
+    for (glyph, origin) in records {
+        out.extend_from_slice(&glyph.to_be_bytes());
+        out.extend_from_slice(&origin.to_be_bytes());
+    }
+    Ok(out)
+}
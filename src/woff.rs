@@ -0,0 +1,404 @@
+//! WOFF / WOFF2 编码
+//!
+//! allsorts 只能读取这两种格式，这里把子集化得到的 sfnt 字节重新封装
+
+use std::io::{Read, Write};
+
+use crate::font_info::BoxError;
+use crate::sfnt::{self, Sfnt, TAG_GLYF, TAG_LOCA, pad4, tag};
+
+const WOFF_SIGNATURE: u32 = tag(b"wOFF");
+const WOFF2_SIGNATURE: u32 = tag(b"wOF2");
+
+/// 封装为 WOFF 1.0，每个表单独 zlib 压缩，压缩后没有变小的保留原样
+pub(crate) fn encode_woff(font: &[u8]) -> Result<Vec<u8>, BoxError> {
+    let sfnt = Sfnt::parse(font)?;
+    let mut tables = sfnt.tables.iter().collect::<Vec<_>>();
+    tables.sort_by_key(|t| t.tag);
+
+    let header_len = 44 + 20 * tables.len();
+    let mut directory = Vec::with_capacity(20 * tables.len());
+    let mut body = Vec::new();
+    let mut total_sfnt_size = 12 + 16 * tables.len();
+    for t in tables.iter() {
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&t.data)?;
+        let compressed = encoder.finish()?;
+        let stored = if compressed.len() < t.data.len() {
+            compressed.as_slice()
+        } else {
+            t.data.as_ref()
+        };
+
+        directory.extend_from_slice(&t.tag.to_be_bytes());
+        directory.extend_from_slice(&((header_len + body.len()) as u32).to_be_bytes());
+        directory.extend_from_slice(&(stored.len() as u32).to_be_bytes());
+        directory.extend_from_slice(&(t.data.len() as u32).to_be_bytes());
+        directory.extend_from_slice(&sfnt::checksum(t.tag, &t.data).to_be_bytes());
+
+        body.extend_from_slice(stored);
+        body.resize(pad4(body.len()), 0);
+        total_sfnt_size += pad4(t.data.len());
+    }
+
+    let mut out = Vec::with_capacity(header_len + body.len());
+    out.extend_from_slice(&WOFF_SIGNATURE.to_be_bytes());
+    out.extend_from_slice(&sfnt.flavor.to_be_bytes());
+    out.extend_from_slice(&((header_len + body.len()) as u32).to_be_bytes());
+    out.extend_from_slice(&(tables.len() as u16).to_be_bytes());
+    out.extend_from_slice(&0u16.to_be_bytes()); // reserved
+    out.extend_from_slice(&(total_sfnt_size as u32).to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes()); // majorVersion
+    out.extend_from_slice(&0u16.to_be_bytes()); // minorVersion
+    out.extend_from_slice(&[0u8; 20]); // 没有 metadata 和 private data
+    out.extend_from_slice(&directory);
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// 封装为 WOFF2
+///
+/// glyf/loca 使用 null transform（版本3），所有表拼接后整体 brotli 压缩
+pub(crate) fn encode_woff2(font: &[u8]) -> Result<Vec<u8>, BoxError> {
+    let sfnt = Sfnt::parse(font)?;
+    let mut tables = sfnt.tables.iter().collect::<Vec<_>>();
+    tables.sort_by_key(|t| t.tag);
+    // loca 紧跟在 glyf 之后
+    if let Some(loca) = tables.iter().position(|t| t.tag == TAG_LOCA) {
+        let loca = tables.remove(loca);
+        let glyf = tables
+            .iter()
+            .position(|t| t.tag == TAG_GLYF)
+            .map(|i| i + 1)
+            .unwrap_or(tables.len());
+        tables.insert(glyf, loca);
+    }
+
+    let mut directory = Vec::new();
+    let mut stream = Vec::new();
+    let mut total_sfnt_size = 12 + 16 * tables.len();
+    for t in tables.iter() {
+        let transform = if t.tag == TAG_GLYF || t.tag == TAG_LOCA {
+            3u8
+        } else {
+            0
+        };
+        match KNOWN_TAGS.iter().position(|k| *k == t.tag) {
+            Some(index) => directory.push(index as u8 | (transform << 6)),
+            None => {
+                directory.push(0x3f | (transform << 6));
+                directory.extend_from_slice(&t.tag.to_be_bytes());
+            }
+        }
+        write_base128(&mut directory, t.data.len() as u32);
+
+        stream.extend_from_slice(&t.data);
+        total_sfnt_size += pad4(t.data.len());
+    }
+
+    let mut compressed = Vec::new();
+    {
+        let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+        writer.write_all(&stream)?;
+    }
+
+    let header_len = 48 + directory.len();
+    let length = pad4(header_len + compressed.len());
+    let mut out = Vec::with_capacity(length);
+    out.extend_from_slice(&WOFF2_SIGNATURE.to_be_bytes());
+    out.extend_from_slice(&sfnt.flavor.to_be_bytes());
+    out.extend_from_slice(&(length as u32).to_be_bytes());
+    out.extend_from_slice(&(tables.len() as u16).to_be_bytes());
+    out.extend_from_slice(&0u16.to_be_bytes()); // reserved
+    out.extend_from_slice(&(total_sfnt_size as u32).to_be_bytes());
+    out.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+    out.extend_from_slice(&1u16.to_be_bytes()); // majorVersion
+    out.extend_from_slice(&0u16.to_be_bytes()); // minorVersion
+    out.extend_from_slice(&[0u8; 20]); // 没有 metadata 和 private data
+    out.extend_from_slice(&directory);
+    out.extend_from_slice(&compressed);
+    out.resize(length, 0);
+    Ok(out)
+}
+
+/// 解压 WOFF 中的每个表，校验长度和 origChecksum
+pub(crate) fn verify_woff(data: &[u8]) -> Result<(), BoxError> {
+    let num_tables = usize::from(sfnt::read_u16(data, 12)?);
+    for i in 0..num_tables {
+        let entry = 44 + i * 20;
+        let table_tag = sfnt::read_u32(data, entry)?;
+        let offset = sfnt::read_u32(data, entry + 4)? as usize;
+        let comp_length = sfnt::read_u32(data, entry + 8)? as usize;
+        let orig_length = sfnt::read_u32(data, entry + 12)? as usize;
+        let orig_checksum = sfnt::read_u32(data, entry + 16)?;
+        let stored = data
+            .get(offset..offset + comp_length)
+            .ok_or_else(|| format!("table {} out of bounds", sfnt::tag_display(table_tag)))?;
+        let mut table = Vec::with_capacity(orig_length);
+        if comp_length < orig_length {
+            flate2::read::ZlibDecoder::new(stored).read_to_end(&mut table)?;
+        } else {
+            table.extend_from_slice(stored);
+        }
+        if table.len() != orig_length || sfnt::checksum(table_tag, &table) != orig_checksum {
+            return Err(format!("table {} is corrupted", sfnt::tag_display(table_tag)).into());
+        }
+    }
+    Ok(())
+}
+
+/// 解压 WOFF2 的数据流，校验长度和表目录一致
+pub(crate) fn verify_woff2(data: &[u8]) -> Result<(), BoxError> {
+    let num_tables = sfnt::read_u16(data, 12)?;
+    let compressed_size = sfnt::read_u32(data, 20)? as usize;
+    let mut offset = 48;
+    let mut expected = 0usize;
+    for _ in 0..num_tables {
+        let (_, length) = read_table_entry(data, &mut offset)?;
+        expected += length as usize;
+    }
+    if sfnt::read_u32(data, 4)? == tag(b"ttcf") {
+        skip_collection_directory(data, &mut offset)?;
+    }
+    let stream = data
+        .get(offset..offset + compressed_size)
+        .ok_or("compressed data out of bounds")?;
+    let mut decompressed = Vec::with_capacity(expected);
+    brotli::Decompressor::new(stream, 4096).read_to_end(&mut decompressed)?;
+    if decompressed.len() != expected {
+        return Err("decompressed size mismatch".into());
+    }
+    Ok(())
+}
+
+/// WOFF2 集合中的字体数量
+///
+/// 集合目录位于表目录之后，需要先跳过长度不固定的表目录
+pub(crate) fn woff2_face_count(data: &[u8]) -> Result<usize, BoxError> {
+    if sfnt::read_u32(data, 4)? != tag(b"ttcf") {
+        return Ok(1);
+    }
+    let num_tables = sfnt::read_u16(data, 12)?;
+    let mut offset = 48;
+    for _ in 0..num_tables {
+        read_table_entry(data, &mut offset)?;
+    }
+    // 跳过集合目录的 version
+    offset += 4;
+    Ok(usize::from(read_255_u16(data, &mut offset)?))
+}
+
+/// 读取一条表目录，返回表标签和数据流中的长度
+fn read_table_entry(data: &[u8], offset: &mut usize) -> Result<(u32, u32), BoxError> {
+    let flags = *data.get(*offset).ok_or("unexpected end of font data")?;
+    *offset += 1;
+    let table_tag = match KNOWN_TAGS.get(usize::from(flags & 0x3f)) {
+        Some(known) => *known,
+        None => {
+            *offset += 4;
+            sfnt::read_u32(data, *offset - 4)?
+        }
+    };
+    let orig_length = read_base128(data, offset)?;
+    let transform = flags >> 6;
+    let transformed = if table_tag == TAG_GLYF || table_tag == TAG_LOCA {
+        transform != 3
+    } else {
+        transform != 0
+    };
+    if transformed {
+        return Ok((table_tag, read_base128(data, offset)?));
+    }
+    Ok((table_tag, orig_length))
+}
+
+/// 跳过集合目录
+fn skip_collection_directory(data: &[u8], offset: &mut usize) -> Result<(), BoxError> {
+    // version
+    *offset += 4;
+    let num_fonts = read_255_u16(data, offset)?;
+    for _ in 0..num_fonts {
+        let num_tables = read_255_u16(data, offset)?;
+        // flavor
+        *offset += 4;
+        for _ in 0..num_tables {
+            read_255_u16(data, offset)?;
+        }
+    }
+    Ok(())
+}
+
+/// 255UInt16 编码
+fn read_255_u16(data: &[u8], offset: &mut usize) -> Result<u16, BoxError> {
+    let byte = |i: usize| {
+        data.get(i)
+            .copied()
+            .ok_or_else(|| BoxError::from("unexpected end of font data"))
+    };
+    let code = byte(*offset)?;
+    *offset += 1;
+    let value = match code {
+        253 => {
+            *offset += 2;
+            sfnt::read_u16(data, *offset - 2)?
+        }
+        254 => {
+            *offset += 1;
+            u16::from(byte(*offset - 1)?) + 506
+        }
+        255 => {
+            *offset += 1;
+            u16::from(byte(*offset - 1)?) + 253
+        }
+        v => u16::from(v),
+    };
+    Ok(value)
+}
+
+fn read_base128(data: &[u8], offset: &mut usize) -> Result<u32, BoxError> {
+    let mut value = 0u32;
+    for _ in 0..5 {
+        let b = *data.get(*offset).ok_or("unexpected end of font data")?;
+        *offset += 1;
+        value = (value << 7) | u32::from(b & 0x7f);
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("invalid UIntBase128".into())
+}
+
+/// UIntBase128 编码，高位在前
+fn write_base128(out: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = [0u8; 5];
+    let mut len = 0;
+    loop {
+        bytes[len] = (value & 0x7f) as u8;
+        len += 1;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+    }
+    for i in (0..len).rev() {
+        out.push(if i == 0 { bytes[i] } else { bytes[i] | 0x80 });
+    }
+}
+
+/// WOFF2 规范中的已知表，下标即 flags 中的 tag index
+const KNOWN_TAGS: [u32; 63] = [
+    tag(b"cmap"),
+    tag(b"head"),
+    tag(b"hhea"),
+    tag(b"hmtx"),
+    tag(b"maxp"),
+    tag(b"name"),
+    tag(b"OS/2"),
+    tag(b"post"),
+    tag(b"cvt "),
+    tag(b"fpgm"),
+    tag(b"glyf"),
+    tag(b"loca"),
+    tag(b"prep"),
+    tag(b"CFF "),
+    tag(b"VORG"),
+    tag(b"EBDT"),
+    tag(b"EBLC"),
+    tag(b"gasp"),
+    tag(b"hdmx"),
+    tag(b"kern"),
+    tag(b"LTSH"),
+    tag(b"PCLT"),
+    tag(b"VDMX"),
+    tag(b"vhea"),
+    tag(b"vmtx"),
+    tag(b"BASE"),
+    tag(b"GDEF"),
+    tag(b"GPOS"),
+    tag(b"GSUB"),
+    tag(b"EBSC"),
+    tag(b"JSTF"),
+    tag(b"MATH"),
+    tag(b"CBDT"),
+    tag(b"CBLC"),
+    tag(b"COLR"),
+    tag(b"CPAL"),
+    tag(b"SVG "),
+    tag(b"sbix"),
+    tag(b"acnt"),
+    tag(b"avar"),
+    tag(b"bdat"),
+    tag(b"bloc"),
+    tag(b"bsln"),
+    tag(b"cvar"),
+    tag(b"fdsc"),
+    tag(b"feat"),
+    tag(b"fmtx"),
+    tag(b"fvar"),
+    tag(b"gvar"),
+    tag(b"hsty"),
+    tag(b"just"),
+    tag(b"lcar"),
+    tag(b"mort"),
+    tag(b"morx"),
+    tag(b"opbd"),
+    tag(b"prop"),
+    tag(b"trak"),
+    tag(b"Zapf"),
+    tag(b"Silf"),
+    tag(b"Glat"),
+    tag(b"Gloc"),
+    tag(b"Feat"),
+    tag(b"Sill"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use allsorts::binary::read::ReadScope;
+    use allsorts::font_data::FontData;
+    use allsorts::tables::FontTableProvider;
+
+    const FONT: &[u8] = include_bytes!("../tests/fonts/Cantarell-Regular.ttf");
+
+    /// 解码后的每个表都和原字体相同
+    fn assert_round_trip(encoded: &[u8]) {
+        let font = ReadScope::new(encoded).read::<FontData>().unwrap();
+        let provider = font.table_provider(0).unwrap();
+        for t in Sfnt::parse(FONT).unwrap().tables.iter() {
+            let data = provider.read_table_data(t.tag).unwrap();
+            assert_eq!(
+                data.as_ref(),
+                t.data.as_ref(),
+                "{}",
+                sfnt::tag_display(t.tag)
+            );
+        }
+    }
+
+    #[test]
+    fn woff_round_trip() {
+        let woff = encode_woff(FONT).unwrap();
+        verify_woff(&woff).unwrap();
+        assert_round_trip(&woff);
+    }
+
+    #[test]
+    fn woff2_round_trip() {
+        let woff2 = encode_woff2(FONT).unwrap();
+        verify_woff2(&woff2).unwrap();
+        assert_eq!(woff2_face_count(&woff2).unwrap(), 1);
+        assert_round_trip(&woff2);
+    }
+
+    #[test]
+    fn base128_round_trip() {
+        for value in [0, 63, 127, 128, 16383, 16384, u32::MAX >> 4] {
+            let mut out = Vec::new();
+            write_base128(&mut out, value);
+            let mut offset = 0;
+            assert_eq!(read_base128(&out, &mut offset).unwrap(), value);
+            assert_eq!(offset, out.len());
+        }
+    }
+}